@@ -0,0 +1,135 @@
+use anyhow::{anyhow, bail, Context, Result};
+use digital_credential_data_models::{elmv3::EuropassEdcCredential, obv3::AchievementCredential};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+
+use crate::{
+    backend::{
+        error::MappingError, preload_p2::get_missing_data_fields, repository::Repository,
+        transformations::Transformation,
+    },
+    trace_dbg,
+};
+
+/// Loads the source credential into a fresh `Repository`, applies all transformations and returns the credential that
+/// was built for the output format, together with the transformations that were skipped because their source is missing.
+pub fn convert_credential(
+    input: Value,
+    transformations: Vec<Transformation>,
+    input_format: &str,
+    output_format: &str,
+) -> Result<(Value, Vec<MappingError>)> {
+    let mut repository = Repository::from(HashMap::from_iter(vec![
+        (input_format.to_string(), input),
+        (output_format.to_string(), json!({})),
+    ]));
+
+    let warnings = repository.apply_transformations(transformations)?;
+
+    let output = repository
+        .get(output_format)
+        .cloned()
+        .ok_or_else(|| anyhow!("no output credential was created for format `{output_format}`"))?;
+
+    Ok((output, warnings))
+}
+
+/// Whether this tool has a data model for the format, of which the name is matched case-insensitively.
+pub fn has_data_model(format: &str) -> bool {
+    matches!(format.to_ascii_uppercase().as_str(), "OBV3" | "ELM")
+}
+
+/// Checks whether the credential deserializes into the data model of the given format. Formats without a data model in
+/// this tool are not checked, see `has_data_model`.
+pub fn validate_credential(format: &str, credential: &Value) -> Result<()> {
+    match format.to_ascii_uppercase().as_str() {
+        "OBV3" => deserialize_as::<AchievementCredential>(credential),
+        "ELM" => deserialize_as::<EuropassEdcCredential>(credential),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the JSON pointers of all mandatory fields that are still missing in the credential, none for formats without
+/// a data model.
+pub fn missing_fields(format: &str, credential: &Value) -> Result<Vec<String>> {
+    match format.to_ascii_uppercase().as_str() {
+        "OBV3" => Ok(get_missing_data_fields::<AchievementCredential>(credential.clone())?),
+        "ELM" => Ok(get_missing_data_fields::<EuropassEdcCredential>(credential.clone())?),
+        _ => Ok(vec![]),
+    }
+}
+
+pub fn read_json<T>(path: impl AsRef<Path>) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("failed to open `{}`", path.display()))?;
+
+    serde_json::from_reader(BufReader::new(file)).with_context(|| format!("failed to parse `{}`", path.display()))
+}
+
+pub fn write_json(path: impl AsRef<Path>, value: &impl Serialize) -> Result<()> {
+    let path = path.as_ref();
+    let json = serde_json::to_string_pretty(value)?;
+
+    std::fs::write(path, json).with_context(|| format!("failed to write `{}`", path.display()))
+}
+
+fn deserialize_as<T>(credential: &Value) -> Result<()>
+where
+    T: DeserializeOwned,
+{
+    let json_as_string = credential.to_string();
+    let mut de = serde_json::Deserializer::from_str(&json_as_string);
+
+    match serde_path_to_error::deserialize::<_, T>(&mut de) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            trace_dbg!(&e);
+            bail!("invalid credential at `{}`: {}", e.path(), e.inner())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_and_reports_skipped_mappings() {
+        let transformations: Vec<Transformation> = serde_json::from_value(json!([
+            {
+                "type_": "copy",
+                "source": { "format": "ELM", "path": "$.title" },
+                "destination": { "format": "OBv3", "path": "$.name" }
+            },
+            {
+                "type_": "copy",
+                "source": { "format": "ELM", "path": "$.missing" },
+                "destination": { "format": "OBv3", "path": "$.description" }
+            },
+            {
+                "type_": "copy",
+                "source": { "format": "ELM", "path": "$.optional", "skip_if_missing": true },
+                "destination": { "format": "OBv3", "path": "$.image" }
+            }
+        ]))
+        .unwrap();
+
+        let (output, warnings) =
+            convert_credential(json!({ "title": "Rust" }), transformations, "ELM", "OBv3").unwrap();
+
+        assert_eq!(output, json!({ "name": "Rust" }));
+        assert_eq!(warnings, [MappingError::PathNotFound("$.missing".to_string())]);
+    }
+
+    #[test]
+    fn formats_without_a_data_model_are_not_checked() {
+        assert!(has_data_model("OBv3") && has_data_model("obv3") && has_data_model("Elm"));
+        assert!(!has_data_model("SPINE"));
+        assert!(validate_credential("SPINE", &json!({ "anything": true })).is_ok());
+        assert!(missing_fields("SPINE", &json!({})).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,41 @@
+use std::fmt;
+
+/// Errors that can occur while loading credentials and mapping files or while applying transformations.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MappingError {
+    /// No credential is loaded for the format, or the format is not supported.
+    UnknownFormat(String),
+    /// The path does not match any value in the credential.
+    PathNotFound(String),
+    /// The value at the pointer does not have the type that is expected there.
+    TypeMismatch { pointer: String, message: String },
+    /// The path matches more values than the data location accepts.
+    TooManyMatches { path: String, matches: usize },
+    /// The JSON Pointer or JSONPath is malformed or cannot be resolved.
+    InvalidPointer(String),
+    /// The parameters of the transformation are invalid or don't fit the value.
+    InvalidTransformation(String),
+    /// The credential does not match the data model of its format.
+    InvalidCredential(String),
+    /// A file could not be opened, read, parsed or written.
+    InvalidFile { path: String, message: String },
+}
+
+impl fmt::Display for MappingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MappingError::UnknownFormat(format) => write!(f, "unknown format `{format}`"),
+            MappingError::PathNotFound(path) => write!(f, "no value found at `{path}`"),
+            MappingError::TypeMismatch { pointer, message } => write!(f, "type mismatch at `{pointer}`: {message}"),
+            MappingError::TooManyMatches { path, matches } => {
+                write!(f, "`{path}` matches {matches} values, expected a single one")
+            }
+            MappingError::InvalidPointer(pointer) => write!(f, "invalid path `{pointer}`"),
+            MappingError::InvalidTransformation(message) => write!(f, "invalid transformation: {message}"),
+            MappingError::InvalidCredential(message) => write!(f, "invalid credential: {message}"),
+            MappingError::InvalidFile { path, message } => write!(f, "invalid file `{path}`: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for MappingError {}
@@ -0,0 +1,436 @@
+use serde::Serialize;
+
+use super::{
+    jsonpointer::{JsonPath, JsonPointer},
+    transformations::{DataLocation, ManyToOne, Matches, OneToMany, OneToOne, Transformation},
+};
+
+/// Summary of an inversion, the mappings are numbered from 1 in the order of the mapping file.
+#[derive(Serialize, Debug, Default)]
+pub struct InversionReport {
+    pub inverted: usize,
+    /// Mappings of which the inverse doesn't restore every value exactly.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lossy: Vec<String>,
+    /// Mappings that can't be inverted and are left out of the inverse mapping.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub not_inverted: Vec<String>,
+}
+
+/// The inverse of a mapping, with the reason why it doesn't restore every value when it is lossy.
+struct Inverse {
+    transformations: Vec<Transformation>,
+    lossy: Option<String>,
+}
+
+impl Inverse {
+    fn exact(transformations: Vec<Transformation>) -> Self {
+        Inverse {
+            transformations,
+            lossy: None,
+        }
+    }
+}
+
+/// Derives the mapping in the opposite direction, by swapping the sources and destinations of all mappings that can be
+/// inverted.
+pub fn invert(transformations: &[Transformation]) -> (Vec<Transformation>, InversionReport) {
+    let mut inverted = vec![];
+    let mut report = InversionReport::default();
+
+    for (index, transformation) in transformations.iter().enumerate() {
+        let name = transformation.describe(index);
+
+        match invert_transformation(transformation) {
+            Ok(inverse) => {
+                report.inverted += 1;
+                if let Some(reason) = inverse.lossy {
+                    report.lossy.push(format!("{name}: {reason}"));
+                }
+                inverted.extend(inverse.transformations);
+            }
+            Err(reason) => report.not_inverted.push(format!("{name}: {reason}")),
+        }
+    }
+
+    (inverted, report)
+}
+
+fn invert_transformation(transformation: &Transformation) -> Result<Inverse, String> {
+    match transformation {
+        Transformation::OneToOne {
+            type_,
+            source,
+            destination,
+        } => {
+            let (type_, lossy) = invert_step(type_)?;
+            let (source, destination, lossy_location) = swap(source, destination)?;
+
+            Ok(Inverse {
+                transformations: vec![Transformation::OneToOne {
+                    type_,
+                    source,
+                    destination,
+                }],
+                lossy: lossy.or(lossy_location),
+            })
+        }
+        Transformation::Pipeline {
+            pipeline,
+            source,
+            destination,
+        } => {
+            // The inverse applies the inverse steps in reverse order
+            let mut lossy = None;
+            let mut inverse_pipeline = vec![];
+            for step in pipeline.iter().rev() {
+                let (step, lossy_step) = invert_step(step)?;
+                lossy = lossy.or(lossy_step);
+                inverse_pipeline.push(step);
+            }
+            let (source, destination, lossy_location) = swap(source, destination)?;
+
+            Ok(Inverse {
+                transformations: vec![Transformation::Pipeline {
+                    pipeline: inverse_pipeline,
+                    source,
+                    destination,
+                }],
+                lossy: lossy.or(lossy_location),
+            })
+        }
+        Transformation::OneToMany {
+            type_: OneToMany::split { dividers },
+            source,
+            destinations,
+        } => {
+            if dividers.chars().count() != 1 {
+                return Err("only a split on a single divider can be joined again".to_string());
+            }
+            Ok(Inverse {
+                transformations: vec![Transformation::ManyToOne {
+                    type_: ManyToOne::join {
+                        separator: dividers.clone(),
+                    },
+                    sources: destinations.iter().map(plain).collect(),
+                    destination: destination_of(source)?,
+                }],
+                lossy: Some("empty parts and parts without a destination are not restored".to_string()),
+            })
+        }
+        Transformation::ManyToOne {
+            type_,
+            sources,
+            destination,
+        } => {
+            let destinations = sources.iter().map(destination_of).collect::<Result<Vec<_>, _>>()?;
+
+            match type_ {
+                ManyToOne::join { separator } if separator.chars().count() == 1 => Ok(Inverse {
+                    transformations: vec![Transformation::OneToMany {
+                        type_: OneToMany::split {
+                            dividers: separator.clone(),
+                        },
+                        source: plain(destination),
+                        destinations,
+                    }],
+                    lossy: Some(
+                        "values that contain the separator, are empty or are not text are not restored".to_string(),
+                    ),
+                }),
+                // Every element of the array goes back to its own source
+                ManyToOne::toArray => destinations
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, source_destination)| {
+                        let pointer =
+                            JsonPointer::try_from(JsonPath(destination.path.clone())).map_err(|e| e.to_string())?;
+                        let element = JsonPointer(format!("{}/{index}", pointer.0));
+
+                        Ok(Transformation::OneToOne {
+                            type_: OneToOne::copy,
+                            source: DataLocation {
+                                format: destination.format.clone(),
+                                path: JsonPath::from(element).to_string(),
+                                ..Default::default()
+                            },
+                            destination: source_destination,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+                    .map(Inverse::exact),
+                ManyToOne::join { .. } => {
+                    Err("only values joined with a single character can be split again".to_string())
+                }
+                ManyToOne::concat => Err("concatenated values can't be split again".to_string()),
+            }
+        }
+        Transformation::ForEach {
+            source,
+            destination,
+            mappings,
+        } => {
+            let (inverse_mappings, report) = invert(mappings);
+            if !report.not_inverted.is_empty() {
+                return Err(format!(
+                    "mappings of the elements can't be inverted: {}",
+                    report.not_inverted.join(", ")
+                ));
+            }
+            let (source, destination, lossy) = swap(source, destination)?;
+
+            Ok(Inverse {
+                transformations: vec![Transformation::ForEach {
+                    source,
+                    destination,
+                    mappings: inverse_mappings,
+                }],
+                lossy: lossy.or_else(|| (!report.lossy.is_empty()).then(|| report.lossy.join(", "))),
+            })
+        }
+        Transformation::Constant { .. } | Transformation::Template { .. } => {
+            Err("the value doesn't come from the source credential".to_string())
+        }
+        Transformation::Conditional { .. } => Err("conditions can't be inverted".to_string()),
+    }
+}
+
+/// Returns the inverse of a step, with the reason why it is lossy.
+fn invert_step(step: &OneToOne) -> Result<(OneToOne, Option<String>), String> {
+    let lossy = |reason: &str| Some(reason.to_string());
+
+    match step {
+        OneToOne::copy => Ok((OneToOne::copy, None)),
+        OneToOne::toLowerCase | OneToOne::toUpperCase => {
+            Ok((OneToOne::copy, lossy("the original case is not restored")))
+        }
+        OneToOne::trim => Ok((OneToOne::copy, lossy("the removed whitespace is not restored"))),
+        OneToOne::lookup {
+            table,
+            default,
+            reverse,
+            loaded,
+        } => {
+            // The inverse reverses the table, which only restores every key when no two keys share a value
+            let injective = loaded.as_ref().map(|loaded| loaded.is_injective());
+            if injective == Some(false) {
+                return Err("several keys translate into the same value, so the table can't be reversed".to_string());
+            }
+            let inverse = OneToOne::lookup {
+                table: table.clone(),
+                default: None,
+                reverse: !reverse,
+                loaded: None,
+            };
+
+            match (default, injective) {
+                (Some(_), _) => Ok((inverse, lossy("values translated into the default are not restored"))),
+                (None, None) => Ok((
+                    inverse,
+                    lossy("the table is not loaded, so it may not restore every key"),
+                )),
+                (None, _) => Ok((inverse, None)),
+            }
+        }
+        OneToOne::toNumber => Ok((
+            OneToOne::toString,
+            lossy("the formatting of the numbers is not restored"),
+        )),
+        OneToOne::toBoolean => Ok((
+            OneToOne::toString,
+            lossy("the spelling of the booleans is not restored"),
+        )),
+        OneToOne::wrapInArray => Ok((
+            OneToOne::unwrapArray,
+            lossy("arrays are not wrapped, so only the ones with a single value are restored"),
+        )),
+        OneToOne::unwrapArray => Ok((
+            OneToOne::wrapInArray,
+            lossy("values that are not arrays are not unwrapped, but they are restored as arrays"),
+        )),
+        OneToOne::slice { .. } | OneToOne::sliceBetween { .. } | OneToOne::regex { .. } => {
+            Err("the removed text can't be restored".to_string())
+        }
+        OneToOne::toDateTime { .. } => Err("the original date format is unknown".to_string()),
+        OneToOne::toString => Err("the original type is unknown".to_string()),
+    }
+}
+
+/// Swaps the source and the destination, with the reason why this is lossy.
+fn swap(
+    source: &DataLocation,
+    destination: &DataLocation,
+) -> Result<(DataLocation, DataLocation, Option<String>), String> {
+    let lossy = (source.matches != Matches::first || !source.fallbacks.is_empty() || source.default.is_some())
+        .then(|| "only the value at the path of the source is restored".to_string());
+
+    Ok((plain(destination), destination_of(source)?, lossy))
+}
+
+/// Returns the source as a destination, which has to point to a single value.
+fn destination_of(source: &DataLocation) -> Result<DataLocation, String> {
+    JsonPointer::try_from(JsonPath(source.path.clone()))
+        .map_err(|_| format!("`{}` matches multiple values and can't be written to", source.path))?;
+
+    Ok(plain(source))
+}
+
+/// Returns the format and the path of the data location, without fallbacks or defaults.
+fn plain(location: &DataLocation) -> DataLocation {
+    DataLocation {
+        format: location.format.clone(),
+        path: location.path.clone(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn location(format: &str, path: &str) -> DataLocation {
+        DataLocation {
+            format: format.to_string(),
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn one_to_one(type_: OneToOne) -> Transformation {
+        Transformation::OneToOne {
+            type_,
+            source: location("ELM", "$.title"),
+            destination: location("OBv3", "$.name"),
+        }
+    }
+
+    fn lookup(content: &str, default: Option<Value>) -> Transformation {
+        let directory = std::env::temp_dir();
+        let name = format!("impierce-mapper-inversion-{}-{}.csv", std::process::id(), content.len());
+        std::fs::write(directory.join(&name), content).unwrap();
+
+        let mut type_ = OneToOne::lookup {
+            table: name.clone(),
+            default,
+            reverse: false,
+            loaded: None,
+        };
+        type_.load_table(&directory).unwrap();
+        std::fs::remove_file(directory.join(name)).unwrap();
+        one_to_one(type_)
+    }
+
+    fn json(transformations: &[Transformation]) -> Value {
+        serde_json::to_value(transformations).unwrap()
+    }
+
+    #[test]
+    fn copies_and_pipelines_are_inverted_exactly() {
+        let pipeline = Transformation::Pipeline {
+            pipeline: vec![OneToOne::copy, OneToOne::copy],
+            source: location("ELM", "$.title"),
+            destination: location("OBv3", "$.name"),
+        };
+        let (inverted, report) = invert(&[one_to_one(OneToOne::copy), pipeline]);
+
+        assert_eq!(report.inverted, 2);
+        assert!(report.lossy.is_empty() && report.not_inverted.is_empty());
+        assert_eq!(
+            json(&inverted),
+            json(&[
+                Transformation::OneToOne {
+                    type_: OneToOne::copy,
+                    source: location("OBv3", "$.name"),
+                    destination: location("ELM", "$.title"),
+                },
+                Transformation::Pipeline {
+                    pipeline: vec![OneToOne::copy, OneToOne::copy],
+                    source: location("OBv3", "$.name"),
+                    destination: location("ELM", "$.title"),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn wrapping_in_arrays_is_lossy_in_both_directions() {
+        for step in [OneToOne::wrapInArray, OneToOne::unwrapArray] {
+            let (inverted, report) = invert(&[one_to_one(step)]);
+
+            assert_eq!(report.inverted, 1);
+            assert_eq!(report.lossy.len(), 1);
+            assert_eq!(inverted.len(), 1);
+        }
+
+        let pipeline = Transformation::Pipeline {
+            pipeline: vec![OneToOne::wrapInArray, OneToOne::copy],
+            source: location("ELM", "$.title"),
+            destination: location("OBv3", "$.name"),
+        };
+        assert_eq!(invert(&[pipeline]).1.lossy.len(), 1);
+    }
+
+    #[test]
+    fn joins_and_splits_are_lossy() {
+        let join = Transformation::ManyToOne {
+            type_: ManyToOne::join {
+                separator: " ".to_string(),
+            },
+            sources: vec![location("ELM", "$.givenName"), location("ELM", "$.familyName")],
+            destination: location("OBv3", "$.name"),
+        };
+        let (inverted, report) = invert(&[join]);
+        assert_eq!(report.lossy.len(), 1);
+        assert!(matches!(inverted[..], [Transformation::OneToMany { .. }]));
+
+        let (inverted, report) = invert(&inverted);
+        assert_eq!(report.lossy.len(), 1);
+        assert!(matches!(inverted[..], [Transformation::ManyToOne { .. }]));
+
+        let concat = Transformation::ManyToOne {
+            type_: ManyToOne::concat,
+            sources: vec![location("ELM", "$.givenName")],
+            destination: location("OBv3", "$.name"),
+        };
+        assert_eq!(invert(&[concat]).1.not_inverted.len(), 1);
+    }
+
+    #[test]
+    fn lookups_are_exact_when_no_keys_share_a_value() {
+        let report = invert(&[lookup("6,Bachelor\n7,Master\n", None)]).1;
+        assert!(report.lossy.is_empty() && report.not_inverted.is_empty());
+
+        let report = invert(&[lookup("6,Bachelor\n7,Master\n", Some(json!("Other")))]).1;
+        assert_eq!(report.lossy.len(), 1);
+
+        let report = invert(&[lookup("6,Bachelor\n6A,Bachelor\n", None)]).1;
+        assert_eq!(report.inverted, 0);
+        assert_eq!(report.not_inverted.len(), 1);
+
+        let unloaded = one_to_one(OneToOne::lookup {
+            table: "levels.csv".to_string(),
+            default: None,
+            reverse: false,
+            loaded: None,
+        });
+        assert_eq!(invert(&[unloaded]).1.lossy.len(), 1);
+    }
+
+    #[test]
+    fn values_that_dont_come_from_the_source_are_left_out() {
+        let constant = Transformation::Constant {
+            value: json!("x"),
+            destination: location("OBv3", "$.name"),
+        };
+        let (inverted, report) = invert(&[
+            constant,
+            one_to_one(OneToOne::toDateTime {
+                timezone: String::new(),
+            }),
+        ]);
+
+        assert!(inverted.is_empty());
+        assert_eq!(report.not_inverted.len(), 2);
+    }
+}
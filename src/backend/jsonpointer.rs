@@ -1,7 +1,11 @@
+use serde_json::Value;
 use std::ops::Deref;
 
+use super::error::MappingError;
+
+/// A JSONPath like `$.credentialSubject['e-Mail']` or `$.achievements[*].title`. Paths that only consist of keys and
+/// array indices can be converted into a `JsonPointer`.
 #[derive(Debug)]
-// TODO: add validation
 pub struct JsonPath(pub String);
 
 impl Deref for JsonPath {
@@ -12,6 +16,8 @@ impl Deref for JsonPath {
     }
 }
 
+/// A JSON Pointer as defined in RFC 6901 like `/credentialSubject/e-Mail`, in which `~` is escaped as `~0` and `/` as
+/// `~1`.
 #[derive(Debug)]
 pub struct JsonPointer(pub String);
 
@@ -23,16 +29,250 @@ impl Deref for JsonPointer {
     }
 }
 
+impl JsonPointer {
+    /// Builds a pointer from unescaped keys and array indices.
+    pub fn from_tokens<T: AsRef<str>>(tokens: impl IntoIterator<Item = T>) -> Self {
+        JsonPointer(
+            tokens
+                .into_iter()
+                .map(|token| format!("/{}", escape_token(token.as_ref())))
+                .collect(),
+        )
+    }
+
+    /// Returns the unescaped keys and array indices of the pointer.
+    pub fn tokens(&self) -> Vec<String> {
+        self.split('/').skip(1).map(unescape_token).collect()
+    }
+}
+
+/// Largest array index a pointer can create, so a path can't fill the credential with millions of nulls.
+pub const MAX_ARRAY_INDEX: usize = 1024;
+
+/// Whether the token is an array index, which RFC 6901 writes without leading zeros.
+pub fn is_array_index(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| c.is_ascii_digit()) && (token == "0" || !token.starts_with('0'))
+}
+
+/// Escapes a key for use in a JSON Pointer.
+pub fn escape_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn unescape_token(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+/// Writes paths that point to a single value as a JSON Pointer, so different notations of the same path are equal.
+pub fn normalize(path: &str) -> String {
+    JsonPointer::try_from(JsonPath(path.to_string()))
+        .map(|pointer| pointer.0)
+        .unwrap_or_else(|_| path.to_string())
+}
+
+impl JsonPath {
+    /// Converts the pointer into a path in the credential. A pointer can't tell an array index from a numeric key, so
+    /// numbers are keys where the credential has an object and array indices everywhere else.
+    pub fn from_pointer(pointer: JsonPointer, credential: &Value) -> JsonPath {
+        let mut current = Some(credential);
+        let path = pointer
+            .tokens()
+            .into_iter()
+            .map(|token| {
+                let parent = current.take();
+                let index = is_array_index(&token) && !parent.is_some_and(Value::is_object);
+                current = match parent {
+                    Some(Value::Object(object)) => object.get(&token),
+                    Some(Value::Array(array)) => token.parse::<usize>().ok().and_then(|index| array.get(index)),
+                    _ => None,
+                };
+                path_segment(&token, index)
+            })
+            .collect::<String>();
+
+        JsonPath(format!("${path}"))
+    }
+}
+
 impl From<JsonPointer> for JsonPath {
+    /// Array indices become `[0]`, keys of letters, digits and underscores become `.key` and all other keys use the
+    /// bracket notation `['key']`. Numbers are taken to be array indices, see `JsonPath::from_pointer` for numeric keys.
     fn from(val: JsonPointer) -> JsonPath {
-        JsonPath(format!("${}", val.replace('/', ".")))
+        let path = val
+            .tokens()
+            .into_iter()
+            .map(|token| path_segment(&token, is_array_index(&token)))
+            .collect::<String>();
+
+        JsonPath(format!("${path}"))
+    }
+}
+
+fn path_segment(token: &str, index: bool) -> String {
+    if index {
+        format!("[{token}]")
+    } else if !token.is_empty()
+        && !token.starts_with(|c: char| c.is_ascii_digit())
+        && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        format!(".{token}")
+    } else {
+        format!("['{}']", token.replace('\\', "\\\\").replace('\'', "\\'"))
     }
 }
 
 impl TryFrom<JsonPath> for JsonPointer {
-    type Error = String;
+    type Error = MappingError;
 
+    /// Only paths of keys and array indices point to a single value, wildcards, filters, slices and recursive descent
+    /// are rejected.
     fn try_from(value: JsonPath) -> Result<Self, Self::Error> {
-        Ok(JsonPointer(value.0.trim_start_matches('$').replace('.', "/")))
+        let invalid = || MappingError::InvalidPointer(value.0.clone());
+
+        let mut chars = value.trim().strip_prefix('$').ok_or_else(invalid)?.chars().peekable();
+        let mut tokens = vec![];
+
+        while let Some(c) = chars.next() {
+            match c {
+                // `.key`, up to the next `.` or `[`
+                '.' => {
+                    let mut token = String::new();
+                    while let Some(&c) = chars.peek() {
+                        if c == '.' || c == '[' {
+                            break;
+                        }
+                        token.push(c);
+                        chars.next();
+                    }
+                    if token.is_empty() || token == "*" {
+                        return Err(invalid());
+                    }
+                    tokens.push(token);
+                }
+                // `['key']`, `["key"]` or `[0]`
+                '[' => {
+                    let token = match chars.peek() {
+                        Some(&quote @ ('\'' | '"')) => {
+                            chars.next();
+                            let mut token = String::new();
+                            loop {
+                                match chars.next().ok_or_else(invalid)? {
+                                    '\\' => token.push(chars.next().ok_or_else(invalid)?),
+                                    c if c == quote => break,
+                                    c => token.push(c),
+                                }
+                            }
+                            token
+                        }
+                        _ => {
+                            let mut token = String::new();
+                            while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                                token.push(c);
+                            }
+                            // Leading zeros are dropped, `[01]` is the same index as `[1]`
+                            token.parse::<usize>().map_err(|_| invalid())?.to_string()
+                        }
+                    };
+                    if chars.next() != Some(']') {
+                        return Err(invalid());
+                    }
+                    tokens.push(token);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(JsonPointer::from_tokens(tokens))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pointer(path: &str) -> String {
+        JsonPointer::try_from(JsonPath(path.to_string())).unwrap().0
+    }
+
+    fn path(pointer: &str) -> String {
+        JsonPath::from(JsonPointer(pointer.to_string())).0
+    }
+
+    #[test]
+    fn escaped_keys_round_trip() {
+        assert_eq!(pointer("$['a~b']['c/d']"), "/a~0b/c~1d");
+        assert_eq!(path("/a~0b/c~1d"), "$['a~b']['c/d']");
+        assert_eq!(pointer(&path("/~01/~10")), "/~01/~10");
+        assert_eq!(JsonPointer("/~01/~10".to_string()).tokens(), ["~1", "/0"]);
+    }
+
+    #[test]
+    fn keys_and_indices_round_trip() {
+        for expected in [
+            "/credentialSubject/achievement/0/name",
+            "/e-Mail",
+            "/it's",
+            "/",
+            "/a/10",
+        ] {
+            assert_eq!(pointer(&path(expected)), expected);
+        }
+        assert_eq!(
+            path("/credentialSubject/achievement/0/name"),
+            "$.credentialSubject.achievement[0].name"
+        );
+        assert_eq!(pointer("$[\"e-Mail\"].x"), "/e-Mail/x");
+    }
+
+    #[test]
+    fn the_root_has_no_tokens() {
+        assert_eq!(pointer("$"), "");
+        assert_eq!(path(""), "$");
+        assert!(JsonPointer(String::new()).tokens().is_empty());
+        assert_eq!(path("/"), "$['']");
+    }
+
+    #[test]
+    fn numeric_keys_are_keys_in_objects() {
+        let credential = json!({ "grades": { "0": "A" }, "items": [{ "0": "B" }] });
+
+        assert_eq!(
+            JsonPath::from_pointer(JsonPointer("/grades/0".to_string()), &credential).0,
+            "$.grades['0']"
+        );
+        assert_eq!(
+            JsonPath::from_pointer(JsonPointer("/items/0/0".to_string()), &credential).0,
+            "$.items[0]['0']"
+        );
+        // Where the credential has no value yet, numbers are array indices
+        assert_eq!(
+            JsonPath::from_pointer(JsonPointer("/missing/0".to_string()), &credential).0,
+            "$.missing[0]"
+        );
+        assert_eq!(pointer("$.grades['0']"), "/grades/0");
+    }
+
+    #[test]
+    fn leading_zeros_are_not_array_indices() {
+        assert_eq!(path("/codes/01"), "$.codes['01']");
+        assert_eq!(pointer(&path("/codes/01")), "/codes/01");
+        assert_eq!(pointer("$.items[01]"), "/items/1");
+        assert!(is_array_index("0") && is_array_index("10"));
+        assert!(!is_array_index("01") && !is_array_index("") && !is_array_index("-1"));
+    }
+
+    #[test]
+    fn paths_with_more_than_keys_are_not_pointers() {
+        for path in [
+            "$.items[*]",
+            "$..name",
+            "$.items[?(@.a)]",
+            "$.items[0:2]",
+            "credentialSubject",
+            "$.a.",
+        ] {
+            assert!(JsonPointer::try_from(JsonPath(path.to_string())).is_err(), "{path}");
+        }
     }
 }
@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use super::{
+    error::MappingError,
+    jsonpointer::{normalize, JsonPath, JsonPointer},
+    mapping_file::load_mapping,
+    transformations::Transformation,
+};
+
+/// A mapping file in the stack of mapping files that is applied to the input credential.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub path: String,
+    pub mappings: Vec<Transformation>,
+}
+
+/// Returns the file name of a layer, to show which layer filled a field.
+pub fn layer_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Loads the mapping files from the lowest to the highest precedence.
+pub fn load_layers(paths: &[String], input_format: &str, output_format: &str) -> Result<Vec<Layer>, MappingError> {
+    paths
+        .iter()
+        .map(|path| {
+            Ok(Layer {
+                path: path.clone(),
+                mappings: load_mapping(path, input_format, output_format)?,
+            })
+        })
+        .collect()
+}
+
+/// Stacks the layers into the mappings that are applied, each with the index of its layer. A mapping of a higher layer
+/// replaces the mappings of lower layers that write one of its destinations, the remaining mappings are applied from the
+/// lowest layer to the highest.
+pub fn stack(layers: &[Layer]) -> Vec<(usize, Transformation)> {
+    let mut stacked: Vec<(usize, Transformation)> = vec![];
+
+    for (index, layer) in layers.iter().enumerate() {
+        let destinations = destinations(&layer.mappings);
+        stacked.retain(|(_, rule)| !writes_any(rule, &destinations));
+        stacked.extend(layer.mappings.iter().map(|rule| (index, rule.clone())));
+    }
+
+    stacked
+}
+
+/// Returns the destinations of the transformations as format and normalized path.
+pub fn destinations(transformations: &[Transformation]) -> Vec<(String, String)> {
+    transformations
+        .iter()
+        .flat_map(Transformation::destinations)
+        .map(|destination| (destination.format.clone(), normalize(&destination.path)))
+        .collect()
+}
+
+/// Whether the transformation writes one of the destinations, given as format and normalized path.
+pub fn writes_any(transformation: &Transformation, destinations: &[(String, String)]) -> bool {
+    transformation.destinations().into_iter().any(|destination| {
+        destinations
+            .iter()
+            .any(|(format, path)| *format == destination.format && *path == normalize(&destination.path))
+    })
+}
+
+/// Returns the layer of the last stacked mapping that writes the pointer of the format, or one of its parents.
+pub fn filled_by(stacked: &[(usize, Transformation)], format: &str, pointer: &str) -> Option<usize> {
+    stacked
+        .iter()
+        .rev()
+        .find(|(_, rule)| {
+            rule.destinations().iter().any(|destination| {
+                destination.format == format
+                    && JsonPointer::try_from(JsonPath(destination.path.clone())).is_ok_and(|destination| {
+                        pointer == destination.as_str() || pointer.starts_with(&format!("{}/", destination.as_str()))
+                    })
+            })
+        })
+        .map(|(layer, _)| *layer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn copy(source: &str, destination: &str) -> Transformation {
+        serde_json::from_value(json!({
+            "type_": "copy",
+            "source": { "format": "ELM", "path": source },
+            "destination": { "format": "OBv3", "path": destination }
+        }))
+        .unwrap()
+    }
+
+    fn layer(path: &str, mappings: Vec<Transformation>) -> Layer {
+        Layer {
+            path: path.to_string(),
+            mappings,
+        }
+    }
+
+    fn sources(stacked: &[(usize, Transformation)]) -> Vec<(usize, String)> {
+        stacked
+            .iter()
+            .map(|(layer, rule)| (*layer, rule.sources()[0].path.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn higher_layers_replace_the_mappings_of_lower_layers() {
+        let layers = [
+            layer("base.json", vec![copy("$.a", "$.id"), copy("$.b", "$.name")]),
+            layer("institution.json", vec![copy("$.c", "$['id']"), copy("$.d", "$.id")]),
+        ];
+        let stacked = stack(&layers);
+
+        // Mappings of the same layer that write the same field are all kept
+        assert_eq!(
+            sources(&stacked),
+            [(0, "$.b".to_string()), (1, "$.c".to_string()), (1, "$.d".to_string())]
+        );
+        assert_eq!(filled_by(&stacked, "OBv3", "/id"), Some(1));
+        assert_eq!(filled_by(&stacked, "OBv3", "/name"), Some(0));
+        assert_eq!(filled_by(&stacked, "OBv3", "/other"), None);
+        assert_eq!(filled_by(&stacked, "ELM", "/id"), None);
+    }
+
+    #[test]
+    fn fields_inside_a_destination_are_filled_by_its_layer() {
+        let stacked = stack(&[layer("base.json", vec![copy("$.a", "$.credentialSubject")])]);
+
+        assert_eq!(filled_by(&stacked, "OBv3", "/credentialSubject/id"), Some(0));
+        assert_eq!(filled_by(&stacked, "OBv3", "/credentialSubjectId"), None);
+    }
+
+    #[test]
+    fn layers_are_named_by_their_file() {
+        assert_eq!(layer_name("res/mapping.json"), "mapping.json");
+        assert_eq!(layer_name("mapping.json"), "mapping.json");
+        assert!(load_layers(&["does/not/exist.json".to_string()], "ELM", "OBv3").is_err());
+    }
+}
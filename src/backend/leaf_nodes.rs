@@ -1,14 +1,27 @@
 use serde_json::Value;
-use std::collections::HashMap;
+use std::{cmp::Ordering, collections::HashMap};
+
+use super::jsonpointer::escape_token;
 
 pub fn extract_leaf_nodes(json_object: &Value, path: String, result: &mut HashMap<String, Value>) {
     match json_object {
         Value::Object(map) => {
             for (key, value) in map {
                 let new_path = if path.is_empty() {
-                    key.clone()
+                    escape_token(key)
+                } else {
+                    format!("{}/{}", path, escape_token(key))
+                };
+                extract_leaf_nodes(value, new_path, result);
+            }
+        }
+        // Every array element is a leaf of its own, addressed by its index. Empty arrays are kept as a leaf.
+        Value::Array(items) if !items.is_empty() => {
+            for (index, value) in items.iter().enumerate() {
+                let new_path = if path.is_empty() {
+                    index.to_string()
                 } else {
-                    format!("{}/{}", path, key)
+                    format!("{}/{}", path, index)
                 };
                 extract_leaf_nodes(value, new_path, result);
             }
@@ -27,3 +40,17 @@ pub fn get_leaf_nodes(json_object: Value) -> HashMap<String, Value> {
         .map(|(key, value)| (format!("/{}", key), value))
         .collect()
 }
+
+/// Orders JSON Pointers by their segments, where array indices are ordered by number: `/a/2` comes before `/a/10`.
+pub fn compare_pointers(a: &str, b: &str) -> Ordering {
+    let segment_order = |(a, b): (&str, &str)| match (a.parse::<usize>(), b.parse::<usize>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    };
+
+    a.split('/')
+        .zip(b.split('/'))
+        .map(segment_order)
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.split('/').count().cmp(&b.split('/').count()))
+}
@@ -0,0 +1,410 @@
+use digital_credential_data_models::{elmv3::EuropassEdcCredential, obv3::AchievementCredential};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::{
+    error::MappingError,
+    jsonpointer::{normalize, JsonPath, JsonPointer},
+    layers::{layer_name, load_layers},
+    preload_p2::{get_json, has_data_field},
+    repository::Repository,
+    transformations::{DataLocation, Transformation},
+};
+use crate::state::AppState;
+
+/// Findings of the linter, the mappings are numbered from 1 in the order of the mapping file.
+#[derive(Serialize, Debug, Default)]
+pub struct LintReport {
+    /// Mappings that are identical to an earlier mapping.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<String>,
+    /// Destinations that are written by more than one mapping.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shared_destinations: Vec<String>,
+    /// Sources that have no value in the sample credential.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing_sources: Vec<String>,
+    /// Destinations that are not a field of the data model of the output format.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_destinations: Vec<String>,
+    /// Mappings of which the output is replaced by a later mapping that writes one of its parents.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub overwritten: Vec<String>,
+    /// Intermediate formats that are written but never read.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unused_formats: Vec<String>,
+}
+
+impl LintReport {
+    /// Returns all findings, each prefixed with the check that reported it.
+    pub fn findings(&self) -> Vec<String> {
+        [
+            ("duplicate", &self.duplicates),
+            ("shared destination", &self.shared_destinations),
+            ("missing source", &self.missing_sources),
+            ("unknown destination", &self.unknown_destinations),
+            ("overwritten", &self.overwritten),
+            ("unused format", &self.unused_formats),
+        ]
+        .into_iter()
+        .flat_map(|(check, findings)| findings.iter().map(move |finding| format!("{check}: {finding}")))
+        .collect()
+    }
+}
+
+/// Checks the mapping for mistakes that don't stop it from being applied. The sources are only checked when a sample
+/// credential in the input format is given.
+pub fn lint(
+    transformations: &[Transformation],
+    input_format: &str,
+    output_format: &str,
+    sample: Option<Value>,
+) -> LintReport {
+    let mut report = LintReport::default();
+    let names: Vec<String> = transformations
+        .iter()
+        .enumerate()
+        .map(|(index, transformation)| transformation.describe(index))
+        .collect();
+
+    // Duplicates are reported once, the other checks only look at the first of identical mappings
+    let values: Vec<Value> = transformations
+        .iter()
+        .map(|transformation| serde_json::to_value(transformation).unwrap_or_default())
+        .collect();
+    let mut distinct = vec![];
+    for (index, value) in values.iter().enumerate() {
+        match values[..index].iter().position(|earlier| earlier == value) {
+            Some(earlier) => report
+                .duplicates
+                .push(format!("{}: identical to mapping {}", names[index], earlier + 1)),
+            None => distinct.push(index),
+        }
+    }
+
+    // Every destination with the mappings that write it, a mapping with several branches counts once
+    let mut writers: BTreeMap<(String, String), (String, Vec<usize>)> = BTreeMap::new();
+    for &index in &distinct {
+        for destination in transformations[index].destinations() {
+            let (_, indices) = writers
+                .entry((destination.format.clone(), normalize(&destination.path)))
+                .or_insert_with(|| (destination.path.clone(), vec![]));
+            if indices.last() != Some(&index) {
+                indices.push(index);
+            }
+        }
+    }
+    for ((format, _), (path, indices)) in &writers {
+        if indices.len() > 1 {
+            report
+                .shared_destinations
+                .push(format!("{format} {path} is written by {}", mappings(indices)));
+        }
+    }
+
+    if let Some(sample) = sample {
+        let repository = Repository::from(HashMap::from_iter([(input_format.to_string(), sample)]));
+        for &index in &distinct {
+            for source in checked_sources(&transformations[index]) {
+                if source.format != input_format {
+                    continue;
+                }
+                // Defaults and skipping hide missing values when the mapping is applied, not here
+                let location = DataLocation {
+                    format: source.format.clone(),
+                    path: source.path.clone(),
+                    fallbacks: source.fallbacks.clone(),
+                    ..Default::default()
+                };
+                if let Err(MappingError::PathNotFound(_)) = repository.find_source_value(&location) {
+                    report
+                        .missing_sources
+                        .push(format!("{}: {} has no value in the sample", names[index], source.path));
+                }
+            }
+        }
+    }
+
+    for &index in &distinct {
+        for destination in transformations[index].destinations() {
+            let Ok(pointer) = JsonPointer::try_from(JsonPath(destination.path.clone())) else {
+                continue;
+            };
+            if destination.format == output_format && !pointer.is_empty() && !in_data_model(output_format, &pointer) {
+                report.unknown_destinations.push(format!(
+                    "{}: {} is not a field of {output_format}",
+                    names[index], destination.path
+                ));
+            }
+        }
+    }
+
+    for (position, &index) in distinct.iter().enumerate() {
+        for destination in transformations[index].destinations() {
+            let Ok(pointer) = JsonPointer::try_from(JsonPath(destination.path.clone())) else {
+                continue;
+            };
+            // The output is lost when a later mapping replaces a parent before anything reads it
+            for &later in &distinct[position + 1..] {
+                if reads(&transformations[later], &destination.format, &pointer) {
+                    break;
+                }
+                if let Some(parent) = replaced_parent(&transformations[later], &destination.format, &pointer) {
+                    report.overwritten.push(format!(
+                        "{}: {} is overwritten by mapping {}, which writes {parent}",
+                        names[index],
+                        destination.path,
+                        later + 1
+                    ));
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut written: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    let mut read = BTreeSet::new();
+    for &index in &distinct {
+        let transformation = &transformations[index];
+        for destination in transformation.destinations() {
+            let indices = written.entry(&destination.format).or_default();
+            if indices.last() != Some(&index) {
+                indices.push(index);
+            }
+        }
+        read.extend(
+            transformation
+                .sources()
+                .into_iter()
+                .map(|source| source.format.as_str()),
+        );
+        if let Transformation::Template { format, .. } = transformation {
+            read.insert(format.as_str());
+        }
+    }
+    for (format, indices) in written {
+        if format != input_format && format != output_format && !read.contains(format) {
+            report
+                .unused_formats
+                .push(format!("{format} is written by {} but never read", mappings(&indices)));
+        }
+    }
+
+    report
+}
+
+/// Lints every layer of mapping files of the first page against its input file. The findings are prefixed with the file
+/// name of their layer, overrides between layers are intended and not reported.
+pub fn preflight(state: &AppState) -> Result<Vec<String>, MappingError> {
+    let (input_format, output_format) = (state.mapping.input_format(), state.mapping.output_format());
+    let sample: Value = get_json(&state.input_path)?;
+
+    let mut findings = vec![];
+    for layer in load_layers(&state.layer_paths(), &input_format, &output_format)? {
+        let report = lint(&layer.mappings, &input_format, &output_format, Some(sample.clone()));
+        let name = layer_name(&layer.path);
+        findings.extend(
+            report
+                .findings()
+                .into_iter()
+                .map(|finding| format!("{name}: {finding}")),
+        );
+    }
+
+    Ok(findings)
+}
+
+/// Returns the sources of which the value is used. A missing value is expected for the source of a condition, also
+/// where a branch reads the value it is guarded by.
+fn checked_sources(transformation: &Transformation) -> Vec<&DataLocation> {
+    match transformation {
+        Transformation::Conditional {
+            condition,
+            then,
+            otherwise,
+        } => {
+            let guard = condition.source();
+            then.iter()
+                .chain(otherwise)
+                .flat_map(checked_sources)
+                .filter(|source| source.format != guard.format || normalize(&source.path) != normalize(&guard.path))
+                .collect()
+        }
+        transformation => transformation.sources(),
+    }
+}
+
+fn in_data_model(format: &str, pointer: &str) -> bool {
+    match format {
+        "OBv3" => has_data_field::<AchievementCredential>(pointer),
+        "ELM" => has_data_field::<EuropassEdcCredential>(pointer),
+        _ => true,
+    }
+}
+
+/// Whether the transformation may read the value at the pointer, or a value inside or around it.
+fn reads(transformation: &Transformation, format: &str, pointer: &str) -> bool {
+    if matches!(transformation, Transformation::Template { format: template_format, .. } if template_format == format) {
+        return true;
+    }
+
+    transformation
+        .sources()
+        .iter()
+        .filter(|source| source.format == format)
+        .flat_map(|source| std::iter::once(&source.path).chain(&source.fallbacks))
+        .any(|path| match JsonPointer::try_from(JsonPath(path.clone())) {
+            Ok(source) => is_within(&source, pointer) || is_within(pointer, &source),
+            // Wildcards and filters may match anything
+            Err(_) => true,
+        })
+}
+
+/// Returns the path of the parent of the pointer that the transformation replaces, if it always writes one.
+fn replaced_parent(transformation: &Transformation, format: &str, pointer: &str) -> Option<String> {
+    let conditional = matches!(transformation, Transformation::Conditional { .. })
+        || transformation.sources().iter().any(|source| source.skip_if_missing);
+    if conditional {
+        return None;
+    }
+
+    transformation
+        .destinations()
+        .into_iter()
+        .filter(|destination| destination.format == format)
+        .find(|destination| {
+            JsonPointer::try_from(JsonPath(destination.path.clone()))
+                .is_ok_and(|parent| parent.as_str() != pointer && is_within(pointer, &parent))
+        })
+        .map(|destination| destination.path.clone())
+}
+
+/// Lists the mappings at the indices by their numbers.
+fn mappings(indices: &[usize]) -> String {
+    let numbers: Vec<String> = indices.iter().map(|index| (index + 1).to_string()).collect();
+
+    match numbers.as_slice() {
+        [number] => format!("mapping {number}"),
+        numbers => format!("mappings {}", numbers.join(", ")),
+    }
+}
+
+/// Whether the pointer is the parent pointer or points inside it.
+fn is_within(pointer: &str, parent: &str) -> bool {
+    pointer == parent || pointer.starts_with(&format!("{parent}/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn copy(source: (&str, &str), destination: (&str, &str)) -> Value {
+        json!({
+            "type_": "copy",
+            "source": { "format": source.0, "path": source.1 },
+            "destination": { "format": destination.0, "path": destination.1 }
+        })
+    }
+
+    // The output format has no data model, so the destinations are not checked against one
+    fn lint_mappings(mappings: Value, sample: Option<Value>) -> LintReport {
+        let transformations: Vec<Transformation> = serde_json::from_value(mappings).unwrap();
+        lint(&transformations, "ELM", "OUT", sample)
+    }
+
+    #[test]
+    fn duplicates_are_reported_once() {
+        let report = lint_mappings(
+            json!([
+                copy(("ELM", "$.a"), ("OUT", "$.id")),
+                copy(("ELM", "$.a"), ("OUT", "$.id")),
+                copy(("ELM", "$.b"), ("OUT", "$['id']")),
+            ]),
+            None,
+        );
+
+        assert_eq!(
+            report.duplicates,
+            ["mapping 2 (ELM $.a -> OUT $.id): identical to mapping 1"]
+        );
+        assert_eq!(report.shared_destinations, ["OUT $.id is written by mappings 1, 3"]);
+    }
+
+    #[test]
+    fn sources_are_checked_against_the_sample() {
+        let mappings = json!([
+            copy(("ELM", "$.a"), ("OUT", "$.a")),
+            copy(("ELM", "$.missing"), ("OUT", "$.b")),
+        ]);
+
+        assert!(lint_mappings(mappings.clone(), None).missing_sources.is_empty());
+        assert_eq!(
+            lint_mappings(mappings, Some(json!({ "a": 1 }))).missing_sources,
+            ["mapping 2 (ELM $.missing -> OUT $.b): $.missing has no value in the sample"]
+        );
+    }
+
+    #[test]
+    fn branches_may_read_the_source_they_are_guarded_by() {
+        let report = lint_mappings(
+            json!([{
+                "if": { "exists": { "source": { "format": "ELM", "path": "$.gender" } } },
+                "then": [
+                    copy(("ELM", "$['gender']"), ("OUT", "$.gender")),
+                    copy(("ELM", "$.missing"), ("OUT", "$.other")),
+                ]
+            }]),
+            Some(json!({ "name": "Jane" })),
+        );
+
+        assert_eq!(
+            report.missing_sources,
+            ["mapping 1 (ELM $.gender -> OUT $.gender): $.missing has no value in the sample"]
+        );
+    }
+
+    #[test]
+    fn outputs_replaced_before_they_are_read_are_overwritten() {
+        let overwritten = lint_mappings(
+            json!([
+                copy(("ELM", "$.a"), ("OUT", "$.b.c")),
+                copy(("ELM", "$.x"), ("OUT", "$.b")),
+            ]),
+            None,
+        );
+        assert_eq!(
+            overwritten.overwritten,
+            ["mapping 1 (ELM $.a -> OUT $.b.c): $.b.c is overwritten by mapping 2, which writes $.b"]
+        );
+
+        let read_first = lint_mappings(
+            json!([
+                copy(("ELM", "$.a"), ("OUT", "$.b.c")),
+                copy(("OUT", "$.b"), ("OUT", "$.d")),
+                copy(("ELM", "$.x"), ("OUT", "$.b")),
+            ]),
+            None,
+        );
+        assert!(read_first.overwritten.is_empty());
+    }
+
+    #[test]
+    fn intermediate_formats_that_are_never_read_are_unused() {
+        let report = lint_mappings(
+            json!([
+                copy(("ELM", "$.a"), ("TMP", "$.a")),
+                copy(("ELM", "$.b"), ("SPINE", "$.b")),
+                copy(("SPINE", "$.b"), ("OUT", "$.b")),
+            ]),
+            None,
+        );
+
+        assert_eq!(report.unused_formats, ["TMP is written by mapping 1 but never read"]);
+        assert_eq!(
+            report.findings(),
+            ["unused format: TMP is written by mapping 1 but never read"]
+        );
+    }
+}
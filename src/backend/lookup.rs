@@ -0,0 +1,170 @@
+use serde_json::{Map, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use super::error::MappingError;
+
+/// Translates values with a user supplied table, e.g. between the controlled vocabularies used by ELM and the codes
+/// used by OBv3.
+#[derive(Debug)]
+pub struct LookupTable {
+    path: String,
+    entries: HashMap<String, Value>,
+}
+
+impl LookupTable {
+    /// Loads a CSV file with a key and a value on every line, or a JSON file containing an object of keys and values.
+    /// A reversed table translates the values back into their keys, so its values have to be unique text.
+    pub fn load(path: impl AsRef<Path>, reverse: bool) -> Result<Self, MappingError> {
+        let path = path.as_ref().display().to_string();
+        let invalid_file = |message: String| MappingError::InvalidFile {
+            path: path.clone(),
+            message,
+        };
+
+        let content = std::fs::read_to_string(&path).map_err(|e| invalid_file(e.to_string()))?;
+
+        let mut entries = if path.to_lowercase().ends_with(".csv") {
+            parse_csv(&content).map_err(invalid_file)?
+        } else {
+            serde_json::from_str::<Map<String, Value>>(&content)
+                .map_err(|e| invalid_file(e.to_string()))?
+                .into_iter()
+                .collect()
+        };
+
+        if reverse {
+            let mut reversed = HashMap::new();
+            for (key, value) in entries {
+                let Value::String(value) = value else {
+                    return Err(invalid_file(format!("only text can be reversed, found {value}")));
+                };
+                if let Some(Value::String(other)) = reversed.insert(value.clone(), Value::String(key.clone())) {
+                    return Err(invalid_file(format!(
+                        "`{other}` and `{key}` both translate into `{value}`, so it can't be reversed"
+                    )));
+                }
+            }
+            entries = reversed;
+        }
+
+        Ok(LookupTable { path, entries })
+    }
+
+    /// Whether no two keys translate into the same value, so the table can be reversed without losing keys.
+    pub fn is_injective(&self) -> bool {
+        let mut values = HashSet::new();
+        self.entries.values().all(|value| values.insert(value.to_string()))
+    }
+
+    /// Returns the value the key translates into. Keys that are not in the table translate into the default, or are
+    /// reported as missing when there is no default.
+    pub fn translate(&self, key: &Value, default: Option<&Value>) -> Result<Value, MappingError> {
+        let key = match key {
+            Value::String(key) => key.clone(),
+            Value::Number(_) | Value::Bool(_) => key.to_string(),
+            _ => {
+                return Err(MappingError::InvalidTransformation(format!(
+                    "{key} can't be looked up in `{}`",
+                    self.path
+                )))
+            }
+        };
+
+        self.entries
+            .get(&key)
+            .or(default)
+            .cloned()
+            .ok_or_else(|| MappingError::InvalidTransformation(format!("`{key}` not found in `{}`", self.path)))
+    }
+}
+
+/// Parses records of `key,value`, empty lines and lines starting with `#` are skipped.
+fn parse_csv(content: &str) -> Result<HashMap<String, Value>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .comment(Some(b'#'))
+        .from_reader(content.as_bytes());
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| e.to_string())?;
+            if record.len() == 2 {
+                return Ok((record[0].to_string(), Value::String(record[1].to_string())));
+            }
+            // The reader doesn't count the skipped lines, so they are counted here
+            let start = record.position().map_or(0, |position| position.byte() as usize);
+            let skipped = content[start..]
+                .lines()
+                .take_while(|line| line.trim().is_empty() || line.starts_with('#'))
+                .count();
+            let line = content[..start].matches('\n').count() + skipped + 1;
+            Err(format!("line {line} is not a `key,value` pair"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn table(name: &str, content: &str, reverse: bool) -> Result<LookupTable, MappingError> {
+        let path = std::env::temp_dir().join(format!("impierce-mapper-lookup-{}-{name}", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let table = LookupTable::load(&path, reverse);
+        std::fs::remove_file(&path).unwrap();
+        table
+    }
+
+    #[test]
+    fn csv_fields_can_be_quoted() {
+        let table = table(
+            "quoted.csv",
+            "# ISCED levels\n\n\"6\",\"Bachelor, or equivalent\"\n7,\"Master \"\"MSc\"\"\"\n",
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            table.translate(&json!(6), None).unwrap(),
+            json!("Bachelor, or equivalent")
+        );
+        assert_eq!(table.translate(&json!("7"), None).unwrap(), json!("Master \"MSc\""));
+        assert!(table.translate(&json!("8"), None).is_err());
+        assert_eq!(
+            table.translate(&json!("8"), Some(&json!("other"))).unwrap(),
+            json!("other")
+        );
+    }
+
+    #[test]
+    fn csv_records_need_a_key_and_a_value() {
+        let error = table("pairs.csv", "# levels\n6,Bachelor\n\n7\n", false).unwrap_err();
+        assert!(error.to_string().contains("line 4"), "{error}");
+    }
+
+    #[test]
+    fn reversed_tables_translate_values_into_keys() {
+        let table = table("reverse.json", r#"{ "6": "Bachelor", "7": "Master" }"#, true).unwrap();
+        assert_eq!(table.translate(&json!("Master"), None).unwrap(), json!("7"));
+    }
+
+    #[test]
+    fn tables_with_duplicate_values_cannot_be_reversed() {
+        assert!(table("duplicate.csv", "6,Bachelor\n7,Master\n", false)
+            .unwrap()
+            .is_injective());
+        assert!(!table("duplicate.csv", "6,Bachelor\n6A,Bachelor\n", false)
+            .unwrap()
+            .is_injective());
+        assert!(table("duplicate.csv", "6,Bachelor\n7,Master\n", true).is_ok());
+        assert!(table("duplicate.csv", "6,Bachelor\n6A,Bachelor\n", true).is_err());
+        assert!(table("number.json", r#"{ "6": 6 }"#, true).is_err());
+    }
+}
@@ -0,0 +1,443 @@
+use chrono::{SecondsFormat, Utc};
+use jsonschema::JSONSchema;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_path_to_error::Segment;
+use std::path::Path;
+
+use super::{error::MappingError, jsonpointer::JsonPointer, spine::Spine, transformations::Transformation};
+
+/// Version of the mapping file format that is written by this version of the tool.
+pub const MAPPING_FILE_VERSION: u64 = 1;
+
+/// The published JSON Schema of the mapping file format.
+pub const MAPPING_SCHEMA: &str = include_str!("../../res/mapping_schema.json");
+
+lazy_static! {
+    /// The schema is compiled once, it is part of the binary so it always compiles.
+    static ref COMPILED_SCHEMA: JSONSchema =
+        JSONSchema::compile(&serde_json::from_str(MAPPING_SCHEMA).unwrap()).unwrap();
+}
+
+/// A mapping file: the mappings from one format to another, with metadata about what they are for.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MappingFile {
+    pub version: u64,
+    #[serde(default)]
+    pub metadata: Metadata,
+    pub mappings: Vec<Transformation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Metadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<FormatVersion>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<FormatVersion>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub author: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// RFC 3339 timestamps of when the file was first and last written.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+    /// Version of the tool that last wrote the file.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub tool_version: String,
+}
+
+/// A credential format and the version of its data model.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FormatVersion {
+    pub format: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub version: String,
+}
+
+impl FormatVersion {
+    /// Returns the format with the version of its data model that this tool supports.
+    pub fn supported(format: &str) -> Self {
+        let version = match format {
+            "OBv3" => "3.0",
+            "ELM" => "3",
+            _ => "",
+        };
+
+        FormatVersion {
+            format: format.to_string(),
+            version: version.to_string(),
+        }
+    }
+}
+
+impl MappingFile {
+    /// Creates a mapping file from the source to the target format, written now by this version of the tool.
+    pub fn new(source: &str, target: &str, mappings: Vec<Transformation>) -> Self {
+        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+
+        MappingFile {
+            version: MAPPING_FILE_VERSION,
+            metadata: Metadata {
+                source: Some(FormatVersion::supported(source)),
+                target: Some(FormatVersion::supported(target)),
+                created: Some(now.clone()),
+                updated: Some(now),
+                tool_version: env!("CARGO_PKG_VERSION").to_string(),
+                ..Default::default()
+            },
+            mappings,
+        }
+    }
+
+    /// Marks the file as written now by this version of the tool.
+    pub fn touch(&mut self) {
+        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+
+        self.metadata.created.get_or_insert_with(|| now.clone());
+        self.metadata.updated = Some(now);
+        self.metadata.tool_version = env!("CARGO_PKG_VERSION").to_string();
+    }
+
+    /// Reads and validates a mapping file. A bare list of mappings, as written by earlier versions, is upgraded to a
+    /// mapping file without metadata.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, MappingError> {
+        let path = path.as_ref();
+        let invalid_file = |message: String| MappingError::InvalidFile {
+            path: path.display().to_string(),
+            message,
+        };
+
+        let text = std::fs::read_to_string(path).map_err(|e| invalid_file(e.to_string()))?;
+        let value: Value = serde_json::from_str(&text).map_err(|e| invalid_file(e.to_string()))?;
+
+        Self::from_value(value, &text).map_err(invalid_file)
+    }
+
+    /// Validates the parsed file against the schema, the text is used to report the line and column of errors.
+    fn from_value(value: Value, text: &str) -> Result<Self, String> {
+        // Legacy files are wrapped, so the errors point into the list of mappings
+        let (value, prefix) = match value {
+            Value::Array(mappings) => (
+                serde_json::json!({ "version": MAPPING_FILE_VERSION, "mappings": mappings }),
+                "/mappings",
+            ),
+            value => (value, ""),
+        };
+
+        let version = value.get("version").and_then(Value::as_u64).unwrap_or_default();
+        if version > MAPPING_FILE_VERSION {
+            return Err(format!(
+                "version {version} is not supported, update the tool to read it (supported: {MAPPING_FILE_VERSION})"
+            ));
+        }
+
+        let position = |pointer: &str| {
+            pointer
+                .strip_prefix(prefix)
+                .and_then(|pointer| locate(text, pointer))
+                .map(|(line, column)| format!("line {line}, column {column}"))
+                .unwrap_or_else(|| format!("`{pointer}`"))
+        };
+
+        if let Err(errors) = COMPILED_SCHEMA.validate(&value) {
+            let errors: Vec<String> = errors
+                .map(|error| format!("{}: {error}", position(&error.instance_path.to_string())))
+                .collect();
+            return Err(errors.join("\n"));
+        }
+
+        // The schema is not as strict as the types, so these errors are located as well
+        serde_path_to_error::deserialize(value).map_err(|e| {
+            let tokens = e.path().iter().filter_map(|segment| match segment {
+                Segment::Seq { index } => Some(index.to_string()),
+                Segment::Map { key } => Some(key.clone()),
+                Segment::Enum { .. } | Segment::Unknown => None,
+            });
+            format!("{}: {}", position(&JsonPointer::from_tokens(tokens)), e.inner())
+        })
+    }
+
+    /// Loads the lookup tables of the mappings, their relative paths are relative to the mapping file at `path`.
+    pub fn load_tables(&mut self, path: &Path) -> Result<(), MappingError> {
+        let base = path.parent().unwrap_or(Path::new(""));
+        self.mappings
+            .iter_mut()
+            .try_for_each(|mapping| mapping.load_tables(base))
+    }
+
+    /// Checks that the file maps from the input format to the output format, when it declares its formats.
+    pub fn check_formats(&self, input_format: &str, output_format: &str) -> Result<(), String> {
+        let declared = |format_version: &Option<FormatVersion>, format: &str| {
+            format_version
+                .as_ref()
+                .is_none_or(|format_version| format_version.format == format)
+        };
+
+        if declared(&self.metadata.source, input_format) && declared(&self.metadata.target, output_format) {
+            return Ok(());
+        }
+        Err(format!("the mappings are not from {input_format} to {output_format}"))
+    }
+}
+
+/// Loads the transformations from the input format to the output format. The file is either a mapping file or a spine
+/// registry, from which the mapping is composed.
+pub fn load_mapping(
+    path: impl AsRef<Path>,
+    input_format: &str,
+    output_format: &str,
+) -> Result<Vec<Transformation>, MappingError> {
+    let path = path.as_ref();
+    let invalid_file = |message: String| MappingError::InvalidFile {
+        path: path.display().to_string(),
+        message,
+    };
+
+    let text = std::fs::read_to_string(path).map_err(|e| invalid_file(e.to_string()))?;
+    let value: Value = serde_json::from_str(&text).map_err(|e| invalid_file(e.to_string()))?;
+
+    if value.get("formats").is_some() {
+        let spine: Spine = serde_json::from_value(value).map_err(|e| invalid_file(e.to_string()))?;
+        return spine.compose(path, input_format, output_format);
+    }
+
+    let mut mapping_file = MappingFile::from_value(value, &text).map_err(invalid_file)?;
+    mapping_file
+        .check_formats(input_format, output_format)
+        .map_err(invalid_file)?;
+    mapping_file.load_tables(path)?;
+
+    Ok(mapping_file.mappings)
+}
+
+/// Returns the line and column, both counting from 1, of the value at the JSON Pointer in the JSON text.
+fn locate(text: &str, pointer: &str) -> Option<(usize, usize)> {
+    let tokens: Vec<String> = pointer
+        .split('/')
+        .skip(1)
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect();
+
+    let mut scanner = Scanner { text, position: 0 };
+    scanner.find(&tokens)?;
+
+    let before = &text[..scanner.position];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+    Some((line, column))
+}
+
+/// Walks through JSON text, which is known to be valid, without building the values.
+struct Scanner<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Scanner<'_> {
+    /// Moves to the start of the value at the tokens, starting at the current value.
+    fn find(&mut self, tokens: &[String]) -> Option<()> {
+        self.skip_whitespace();
+        let Some((token, rest)) = tokens.split_first() else {
+            return Some(());
+        };
+
+        match self.peek()? {
+            '{' => {
+                self.position += 1;
+                loop {
+                    self.skip_whitespace();
+                    if self.peek()? == '}' {
+                        return None;
+                    }
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    self.position += 1; // :
+                    if key == *token {
+                        return self.find(rest);
+                    }
+                    self.skip_value()?;
+                    self.skip_whitespace();
+                    if self.peek()? == ',' {
+                        self.position += 1;
+                    }
+                }
+            }
+            '[' => {
+                let index: usize = token.parse().ok()?;
+                self.position += 1;
+                for _ in 0..index {
+                    self.skip_value()?;
+                    self.skip_whitespace();
+                    if self.peek()? != ',' {
+                        return None;
+                    }
+                    self.position += 1;
+                }
+                // The array ends before the index
+                self.skip_whitespace();
+                if self.peek()? == ']' {
+                    return None;
+                }
+                self.find(rest)
+            }
+            _ => None,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    /// Reads a string and returns its unescaped content.
+    fn string(&mut self) -> Option<String> {
+        let start = self.position;
+        self.skip_value()?;
+        serde_json::from_str(&self.text[start..self.position]).ok()
+    }
+
+    fn skip_value(&mut self) -> Option<()> {
+        self.skip_whitespace();
+        match self.peek()? {
+            '"' => {
+                self.position += 1;
+                let mut escaped = false;
+                for (offset, c) in self.text[self.position..].char_indices() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => {
+                            self.position += offset + 1;
+                            return Some(());
+                        }
+                        _ => escaped = false,
+                    }
+                }
+                None
+            }
+            '{' | '[' => {
+                // Strings are skipped as a whole, so the brackets in them are not counted
+                self.position += 1;
+                let mut depth = 1;
+                while depth > 0 {
+                    self.skip_whitespace();
+                    match self.peek()? {
+                        '"' => self.skip_value()?,
+                        '{' | '[' => {
+                            depth += 1;
+                            self.position += 1;
+                        }
+                        '}' | ']' => {
+                            depth -= 1;
+                            self.position += 1;
+                        }
+                        c => self.position += c.len_utf8(),
+                    }
+                }
+                Some(())
+            }
+            _ => {
+                let end = self.text[self.position..]
+                    .find(|c: char| c == ',' || c == '}' || c == ']' || c.is_whitespace())
+                    .unwrap_or(self.text.len() - self.position);
+                self.position += end;
+                Some(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = r#"{
+  "a": { "b~/c": [1, "x]}", { "d": "e" }] },
+  "f": [
+    [],
+    { "g\"h": null }
+  ]
+}"#;
+
+    #[test]
+    fn values_are_located_by_line_and_column() {
+        assert_eq!(locate(TEXT, ""), Some((1, 1)));
+        assert_eq!(locate(TEXT, "/a"), Some((2, 8)));
+        assert_eq!(locate(TEXT, "/a/b~0~1c/0"), Some((2, 19)));
+        // The brackets in the string are not counted
+        assert_eq!(locate(TEXT, "/a/b~0~1c/2/d"), Some((2, 36)));
+        assert_eq!(locate(TEXT, "/f/1/g\"h"), Some((5, 15)));
+    }
+
+    #[test]
+    fn missing_values_are_not_located() {
+        assert_eq!(locate(TEXT, "/x"), None);
+        assert_eq!(locate(TEXT, "/a/b~0~1c/3"), None);
+        assert_eq!(locate(TEXT, "/f/0/0"), None);
+        assert_eq!(locate(TEXT, "/f/one"), None);
+    }
+
+    fn read(text: &str) -> Result<MappingFile, String> {
+        MappingFile::from_value(serde_json::from_str(text).unwrap(), text)
+    }
+
+    #[test]
+    fn schema_errors_are_located() {
+        let error = read(
+            r#"{
+  "version": 1,
+  "mappings": [
+    { "type_": "copi", "source": { "format": "ELM", "path": "$.id" }, "destination": { "format": "OBv3", "path": "$.id" } }
+  ]
+}"#,
+        )
+        .unwrap_err();
+
+        assert!(error.starts_with("line 4, column 16: "), "{error}");
+    }
+
+    #[test]
+    fn errors_of_the_types_are_located() {
+        // A number this large is an integer to the schema, but doesn't fit the type of `start`
+        let error = read(
+            r#"{
+  "version": 1,
+  "mappings": [
+    {
+      "type_": { "slice": { "start": 100000000000000000000 } },
+      "source": { "format": "ELM", "path": "$.id" },
+      "destination": { "format": "OBv3", "path": "$.id" }
+    }
+  ]
+}"#,
+        )
+        .unwrap_err();
+
+        assert!(error.starts_with("line 4, column 5: "), "{error}");
+    }
+
+    #[test]
+    fn legacy_lists_are_located_in_the_list() {
+        let error = read(r#"[{ "type_": "copy" }]"#).unwrap_err();
+        assert!(error.starts_with("line 1, column 2: "), "{error}");
+        assert!(read("[]").is_ok_and(|file| file.mappings.is_empty()));
+    }
+
+    #[test]
+    fn newer_versions_and_other_formats_are_refused() {
+        assert!(read(r#"{ "version": 2, "mappings": [] }"#)
+            .unwrap_err()
+            .contains("version 2"));
+
+        let file = read(r#"{ "version": 1, "metadata": { "source": { "format": "ELM" } }, "mappings": [] }"#).unwrap();
+        assert!(file.check_formats("ELM", "OBv3").is_ok());
+        assert!(file.check_formats("OBv3", "ELM").is_err());
+    }
+}
@@ -1,7 +1,16 @@
+pub mod conversion;
+pub mod error;
+pub mod inversion;
 pub mod jsonpointer;
+pub mod layers;
 pub mod leaf_nodes;
+pub mod linter;
 pub mod logging;
+pub mod lookup;
+pub mod mapping_file;
 pub mod preload_p2;
 pub mod repository;
 pub mod selector;
+pub mod session;
+pub mod spine;
 pub mod transformations;
@@ -1,90 +1,121 @@
-use core::panic;
 use digital_credential_data_models::{elmv3::EuropassEdcCredential, obv3::AchievementCredential};
 use regex::Regex;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::{json, Value};
-use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::BufReader,
+    path::Path,
+};
 
 use super::repository::{construct_leaf_node, merge};
 use crate::{
-    backend::{leaf_nodes::get_leaf_nodes, repository::Repository, transformations::Transformation},
+    backend::{
+        error::MappingError,
+        layers::{filled_by, layer_name, load_layers, stack},
+        leaf_nodes::{compare_pointers, get_leaf_nodes},
+        repository::Repository,
+    },
     state::AppState,
     trace_dbg,
 };
 
 // todo: when going back to p1 and loading again, everything in backend is wiped because of this preload fn.
 // this is fine but then also state info must be wiped
-pub fn preload_p2(state: &mut AppState) {
+pub fn preload_p2(state: &mut AppState) -> Result<(), MappingError> {
     let (input_format, output_format) = (state.mapping.input_format(), state.mapping.output_format());
 
     // Load the input file
     {
-        let rdr = std::fs::File::open(&state.input_path).unwrap();
-        let input_value: Value = serde_json::from_reader(rdr).unwrap();
-        let leaf_nodes: HashMap<String, Value> = get_leaf_nodes(input_value);
+        let input_value: Value = get_json(&state.input_path)?;
+        let leaf_nodes: HashMap<String, Value> = get_leaf_nodes(input_value.clone());
         let mut input_fields = vec![(String::new(), String::new())];
 
         for (key, value) in leaf_nodes {
             input_fields.push((key, value.to_string()));
         }
 
-        input_fields.sort();
-        state.amount_input_fields = input_fields.len() - 2;
+        input_fields.sort_by(|(a, _), (b, _)| compare_pointers(a, b));
+        state.amount_input_fields = input_fields.len().saturating_sub(2);
         state.input_fields = input_fields;
 
         state.repository = Repository::from(HashMap::from_iter(vec![
-            (
-                input_format.to_string(),
-                get_json(&state.input_path).expect("No source file found"),
-            ),
+            (input_format.to_string(), input_value),
             (output_format.to_string(), json!({})),
         ]));
 
         trace_dbg!("Successfully loaded the input file");
     }
 
-    // Load the mapping file
-    {
-        let rdr = std::fs::File::open(&state.mapping_path).unwrap();
-        let transformations: Vec<Transformation> = serde_json::from_reader(rdr).unwrap();
+    // Load the layers of mapping files, a higher layer overrides the mappings of lower layers for the same fields
+    let layer_paths = state.layer_paths();
+    let stacked = {
+        let layers = load_layers(&layer_paths, &input_format, &output_format)?;
+        let stacked = stack(&layers);
 
-        trace_dbg!("Successfully loaded the mapping file");
+        trace_dbg!("Successfully loaded the mapping files");
 
-        // Load the custom mapping file
-        // { // todo: I added the custom_mapping_path as the destination to save the manual mappings to. not to load mappings from, thats the mapping_path
-        //     if !state.custom_mapping_path.is_empty() && Path::new(&state.custom_mapping_path).is_file() && state.custom_mapping_path.ends_with(".json"){
-        //         let rdr = std::fs::File::open(&state.custom_mapping_path).unwrap();
-        //         let mut custom_transformations: Vec<Transformation> = serde_json::from_reader(rdr).unwrap();
-        //         transformations.append(&mut custom_transformations);
+        let transformations = stacked
+            .iter()
+            .map(|(_, transformation)| transformation.clone())
+            .collect();
+        let warnings = state.repository.apply_transformations(transformations)?;
+        trace_dbg!(&warnings);
 
-        //         trace_dbg!("Successfully loaded the custom mapping file");
-        //     }
-        // }
+        // The manual mappings are applied again on this repository when they are edited
+        state.loaded_repository = Repository::from(state.repository.clone());
+        state.history.clear();
 
-        state.repository.apply_transformations(transformations);
-    }
+        stacked
+    };
 
     trace_dbg!(&output_format);
     trace_dbg!(&state.repository);
-    let json_value = state.repository.get(&output_format).unwrap().clone();
+    let json_value = state
+        .repository
+        .get(&output_format)
+        .ok_or_else(|| MappingError::UnknownFormat(output_format.clone()))?
+        .clone();
 
+    let mut layer_fields: Vec<(String, String, String)> = get_leaf_nodes(json_value.clone())
+        .into_iter()
+        .filter_map(|(pointer, value)| {
+            let layer = filled_by(&stacked, &output_format, &pointer)?;
+            Some((pointer, value.to_string(), layer_name(&layer_paths[layer])))
+        })
+        .collect();
+    layer_fields.sort_by(|(a, ..), (b, ..)| compare_pointers(a, b));
+
+    let missing_fields = match output_format.as_str() {
+        "OBv3" => get_missing_data_fields::<AchievementCredential>(json_value.clone())?,
+        "ELM" => get_missing_data_fields::<EuropassEdcCredential>(json_value.clone())?,
+        _ => return Err(MappingError::UnknownFormat(output_format)),
+    };
+    state.missing_field_layers = vec![String::new(); missing_fields.len() + 1];
     state.missing_data_fields = [
         vec![("".to_string(), "".to_string())],
-        match state.mapping.output_format().as_str() {
-            "OBv3" => get_missing_data_fields::<AchievementCredential>(json_value.clone()),
-            "ELM" => get_missing_data_fields::<EuropassEdcCredential>(json_value.clone()),
-            _ => panic!(),
-        }
-        .into_iter()
-        .map(|pointer| (pointer, "".to_string()))
-        .collect(),
+        missing_fields
+            .into_iter()
+            .map(|pointer| (pointer, "".to_string()))
+            .collect(),
     ]
     .concat();
 
+    // The fields the mapping files filled are listed below the missing fields, so they can be mapped again
+    for (pointer, value, layer) in layer_fields {
+        state.missing_data_fields.push((pointer, value));
+        state.missing_field_layers.push(layer);
+    }
+
     //selector(state);
+
+    Ok(())
 }
 
-pub fn verify<T>(json_value: &mut Value) -> Result<Value, String>
+/// Tries to deserialize the credential into its data model, filling in placeholders for missing fields along the way.
+/// Returns a `MappingError::TypeMismatch` with the pointer of the first field that still needs a value.
+pub fn verify<T>(json_value: &mut Value) -> Result<Value, MappingError>
 where
     T: DeserializeOwned + Serialize,
 {
@@ -99,19 +130,24 @@ where
             Err(e) => {
                 let error_message = e.inner().to_string();
 
-                let path = e.path().to_string().replace('.', "/");
+                // Sequence elements like `achievement[0]` become the array index `achievement/0`
+                let path = e.path().to_string().replace(['.', '['], "/").replace(']', "");
 
                 if error_message.starts_with("missing field") {
-                    let missing_field = extract_between_backticks(&e.to_string()).unwrap();
+                    let missing_field = extract_between_backticks(&e.to_string())
+                        .ok_or_else(|| MappingError::InvalidCredential(error_message.clone()))?;
                     let pointer = if path == "/" {
                         format!("{path}{missing_field}")
                     } else {
                         format!("/{path}/{missing_field}")
                     };
 
-                    let mut leaf_node = construct_leaf_node(&pointer);
+                    let mut leaf_node = construct_leaf_node(&pointer)?;
 
-                    leaf_node.pointer_mut(&pointer).map(|value| *value = json!({})).unwrap(); // could be a problem when we add field constraints
+                    leaf_node
+                        .pointer_mut(&pointer)
+                        .map(|value| *value = json!({}))
+                        .ok_or_else(|| MappingError::InvalidPointer(pointer.clone()))?; // could be a problem when we add field constraints
 
                     merge(json_value, leaf_node);
 
@@ -123,9 +159,12 @@ where
                 if error_message.starts_with("data did not match any variant of untagged enum") {
                     let pointer = if path == "/" { path } else { format!("/{path}") };
 
-                    let mut leaf_node = construct_leaf_node(&pointer);
+                    let mut leaf_node = construct_leaf_node(&pointer)?;
 
-                    leaf_node.pointer_mut(&pointer).map(|value| *value = json!("")).unwrap(); // doesnt work
+                    leaf_node
+                        .pointer_mut(&pointer)
+                        .map(|value| *value = json!(""))
+                        .ok_or_else(|| MappingError::InvalidPointer(pointer.clone()))?; // doesnt work
 
                     merge(json_value, leaf_node);
 
@@ -137,12 +176,12 @@ where
                 if error_message.starts_with("input contains invalid characters") {
                     let pointer = if path == "/" { path } else { format!("/{path}") };
 
-                    let mut leaf_node = construct_leaf_node(&pointer);
+                    let mut leaf_node = construct_leaf_node(&pointer)?;
 
                     leaf_node
                         .pointer_mut(&pointer)
                         .map(|value| *value = json!("2010-01-01T00:00:00Z"))
-                        .unwrap();
+                        .ok_or_else(|| MappingError::InvalidPointer(pointer.clone()))?;
 
                     merge(json_value, leaf_node);
 
@@ -154,18 +193,21 @@ where
                 if error_message.starts_with("invalid value") {
                     let pointer = if path == "/" { path } else { format!("/{path}") };
 
-                    let mut leaf_node = construct_leaf_node(&pointer);
+                    let mut leaf_node = construct_leaf_node(&pointer)?;
 
-                    let expected_value = extract_string_value(&error_message).unwrap();
+                    let expected_value = extract_string_value(&error_message)
+                        .ok_or_else(|| MappingError::InvalidCredential(error_message.clone()))?;
 
                     if expected_value != "https://www.w3.org/ns/credentials/v2" {
-                        panic!("Expected value: {}", expected_value);
+                        return Err(MappingError::InvalidCredential(format!(
+                            "expected value {expected_value} at `{pointer}`"
+                        )));
                     }
 
                     leaf_node
                         .pointer_mut(&pointer)
                         .map(|value| *value = json!([expected_value]))
-                        .unwrap();
+                        .ok_or_else(|| MappingError::InvalidPointer(pointer.clone()))?;
 
                     merge(json_value, leaf_node);
 
@@ -180,39 +222,50 @@ where
                     {
                         let pointer = if path == "/" { path } else { format!("/{path}") };
 
-                        let mut leaf_node = construct_leaf_node(&pointer);
+                        let mut leaf_node = construct_leaf_node(&pointer)?;
 
                         leaf_node
                             .pointer_mut(&pointer)
                             .map(|value| *value = json!(["TEMP"]))
-                            .unwrap();
+                            .ok_or_else(|| MappingError::InvalidPointer(pointer.clone()))?;
 
                         merge(json_value, leaf_node);
 
                         // json_as_string = json_value.to_string();
 
-                        return Err(format!("{pointer}/0"));
+                        return Err(MappingError::TypeMismatch {
+                            pointer: format!("{pointer}/0"),
+                            message: error_message,
+                        });
                     } else if path == "/" {
                         path
                     } else {
                         format!("/{path}")
                     };
 
-                    return Err(pointer);
+                    return Err(MappingError::TypeMismatch {
+                        pointer,
+                        message: error_message,
+                    });
                 } else {
-                    panic!("Unknown error: {}", error_message);
+                    return Err(MappingError::InvalidCredential(error_message));
                 };
             }
         }
     }
 }
 
-pub fn get_missing_data_fields<T>(mut temp_credential: Value) -> Vec<String>
+pub fn get_missing_data_fields<T>(mut temp_credential: Value) -> Result<Vec<String>, MappingError>
 where
     T: DeserializeOwned + Serialize,
 {
     let mut missing_data_fields = vec![];
-    while let Err(pointer) = verify::<T>(&mut temp_credential) {
+    loop {
+        let pointer = match verify::<T>(&mut temp_credential) {
+            Ok(_) => return Ok(missing_data_fields),
+            Err(MappingError::TypeMismatch { pointer, .. }) => pointer,
+            Err(e) => return Err(e),
+        };
         trace_dbg!(&temp_credential);
         trace_dbg!(&pointer);
 
@@ -221,12 +274,42 @@ where
             .map(|value| *value = json!("TEMP")) // could be a problem when we add field constraints
         {
             Some(_) => {}
-            None => return vec![],
+            None => return Ok(vec![]),
         }
         missing_data_fields.push(pointer);
     }
+}
+
+/// Checks whether the data model has a field at the pointer. A credential with only a placeholder at the pointer is
+/// completed until it deserializes, the placeholder is dropped when the credential is serialized again if the data model
+/// doesn't know the field. Fields of which this can't be decided are assumed to exist.
+pub fn has_data_field<T>(pointer: &str) -> bool
+where
+    T: DeserializeOwned + Serialize,
+{
+    let Ok(mut temp_credential) = construct_leaf_node(pointer) else {
+        return true;
+    };
+    match temp_credential.pointer_mut(pointer) {
+        Some(value) => *value = json!("TEMP"),
+        None => return true,
+    }
 
-    missing_data_fields
+    let mut filled = HashSet::new();
+    loop {
+        match verify::<T>(&mut temp_credential) {
+            Ok(credential) => return credential.pointer(pointer).is_some(),
+            // The data model expects another type at the pointer, so it has the field
+            Err(MappingError::TypeMismatch { pointer: mismatch, .. }) if mismatch == pointer => return true,
+            Err(MappingError::TypeMismatch { pointer: mismatch, .. }) if filled.insert(mismatch.clone()) => {
+                match temp_credential.pointer_mut(&mismatch) {
+                    Some(value) => *value = json!("TEMP"),
+                    None => return true,
+                }
+            }
+            Err(_) => return true,
+        }
+    }
 }
 
 fn extract_string_value(input: &str) -> Option<&str> {
@@ -234,14 +317,19 @@ fn extract_string_value(input: &str) -> Option<&str> {
     re.captures(input).and_then(|cap| cap.get(1).map(|m| m.as_str()))
 }
 
-fn get_json<T>(path: impl AsRef<Path>) -> Result<T, serde_json::Error>
+pub fn get_json<T>(path: impl AsRef<Path>) -> Result<T, MappingError>
 where
     T: DeserializeOwned,
 {
-    let file = File::open(path).expect("failed to open file");
+    let invalid_file = |message: String| MappingError::InvalidFile {
+        path: path.as_ref().display().to_string(),
+        message,
+    };
+
+    let file = File::open(&path).map_err(|e| invalid_file(e.to_string()))?;
     let reader = BufReader::new(file);
 
-    serde_json::from_reader(reader)
+    serde_json::from_reader(reader).map_err(|e| invalid_file(e.to_string()))
 }
 
 fn extract_between_backticks(s: &str) -> Option<String> {
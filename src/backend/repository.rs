@@ -1,12 +1,14 @@
 use crate::{
     backend::{
-        jsonpointer::{JsonPath, JsonPointer},
-        transformations::{DataLocation, Transformation},
+        error::MappingError,
+        jsonpointer::{is_array_index, JsonPath, JsonPointer, MAX_ARRAY_INDEX},
+        transformations::{Condition, DataLocation, Matches, Transformation},
     },
     state::AppState,
     trace_dbg,
 };
 use jsonpath_rust::JsonPathFinder;
+use regex::Regex;
 use serde_json::{json, Map, Value};
 use std::{
     collections::HashMap,
@@ -41,132 +43,590 @@ impl Repository {
     //     Self(HashMap::new())
     // }
 
-    pub fn apply_transformation(&mut self, transformation: Transformation) {
+    /// Applies the transformation to the credentials in the repository. A transformation of which a source is missing
+    /// and may be skipped is not applied, one whose source has no value is skipped and returned as a warning.
+    pub fn apply_transformation(&mut self, transformation: Transformation) -> Result<Vec<MappingError>, MappingError> {
+        match self.apply_mapping(transformation) {
+            Err(warning @ MappingError::PathNotFound(_)) => Ok(vec![warning]),
+            result => result,
+        }
+    }
+
+    fn apply_mapping(&mut self, transformation: Transformation) -> Result<Vec<MappingError>, MappingError> {
         match transformation {
             Transformation::OneToOne {
                 type_: transformation,
-                source:
-                    DataLocation {
-                        format: source_format,
-                        path: mut source_path,
-                    },
-                destination:
-                    DataLocation {
-                        format: destination_format,
-                        path: destination_path,
-                    },
+                source,
+                destination,
             } => {
-                let source_credential = self.get(&source_format).unwrap();
-
-                if source_path == "$.@context" {
-                    source_path = r#"$["@context"]"#.to_string();
+                let Some(source_value) = self.find_source_value(&source)? else {
+                    return Ok(vec![]);
                 };
-                let finder = JsonPathFinder::from_str(&source_credential.to_string(), &source_path).unwrap();
-                let source_value = finder.find().as_array().unwrap().first().unwrap().clone();
-
-                let destination_credential = self.entry(destination_format).or_insert(json!({}));
-                let pointer = JsonPointer::try_from(JsonPath(destination_path)).unwrap();
-
-                let mut leaf_node = construct_leaf_node(&pointer);
 
-                if let Some(value) = leaf_node.pointer_mut(&pointer) {
-                    *value = transformation.apply(source_value);
-                }
-
-                merge(destination_credential, leaf_node);
+                self.write_destination(destination, transformation.apply(source_value)?)?;
+                Ok(vec![])
             }
             Transformation::ManyToOne {
                 type_: transformation,
                 sources,
                 destination,
             } => {
-                let source_values = sources
+                let Some(source_values) = sources
                     .iter()
-                    .map(|source| {
-                        let source_credential = self.get(&source.format).unwrap();
+                    .map(|source| self.find_source_value(source))
+                    .collect::<Result<Option<Vec<_>>, _>>()?
+                else {
+                    return Ok(vec![]);
+                };
 
-                        let finder = JsonPathFinder::from_str(&source_credential.to_string(), &source.path).unwrap();
-                        finder.find().as_array().unwrap().first().unwrap().clone()
-                    })
-                    .collect::<Vec<_>>();
+                self.write_destination(destination, transformation.apply(source_values))?;
+                Ok(vec![])
+            }
+            Transformation::OneToMany {
+                type_: transformation,
+                source,
+                destinations,
+            } => {
+                let Some(source_value) = self.find_source_value(&source)? else {
+                    return Ok(vec![]);
+                };
 
-                let destination_credential = self.entry(destination.format).or_insert(json!({}));
-                let pointer = JsonPointer::try_from(JsonPath(destination.path.clone())).unwrap();
+                // Every part is written to the destination at the same index. Destinations without a part are left
+                // untouched and parts without a destination are dropped.
+                for (destination, value) in destinations.into_iter().zip(transformation.apply(source_value)) {
+                    self.write_destination(destination, value)?;
+                }
+                Ok(vec![])
+            }
+            Transformation::Pipeline {
+                pipeline,
+                source,
+                destination,
+            } => {
+                let Some(source_value) = self.find_source_value(&source)? else {
+                    return Ok(vec![]);
+                };
+                let value = pipeline
+                    .iter()
+                    .try_fold(source_value, |value, step| step.apply(value))?;
 
-                let mut leaf_node = construct_leaf_node(&pointer);
+                self.write_destination(destination, value)?;
+                Ok(vec![])
+            }
+            Transformation::Constant { value, destination } => {
+                self.write_destination(destination, value)?;
+                Ok(vec![])
+            }
+            Transformation::Template {
+                template,
+                format,
+                destination,
+            } => {
+                let value = self.fill_template(&template, &format)?;
 
-                if let Some(value) = leaf_node.pointer_mut(&pointer) {
-                    *value = transformation.apply(source_values);
+                self.write_destination(destination, Value::String(value))?;
+                Ok(vec![])
+            }
+            Transformation::ForEach {
+                source,
+                destination,
+                mappings,
+            } => {
+                let Some(source_value) = self.find_source_value(&source)? else {
+                    return Ok(vec![]);
+                };
+                let Value::Array(elements) = source_value else {
+                    return Err(MappingError::TypeMismatch {
+                        pointer: source.path,
+                        message: "expected an array".to_string(),
+                    });
+                };
+
+                // Every element is mapped in a repository of its own, holding only the element in the source format.
+                // Fields that are missing in an element are left out of its result and reported with the path of the
+                // element.
+                let mut values = vec![];
+                let mut warnings = vec![];
+                for (index, element) in elements.into_iter().enumerate() {
+                    let mut element_repository =
+                        Repository::from(HashMap::from_iter([(source.format.clone(), element)]));
+                    let in_element = |warning| match warning {
+                        MappingError::PathNotFound(path) => MappingError::PathNotFound(format!(
+                            "{}[{index}]{}",
+                            source.path,
+                            path.strip_prefix('$').unwrap_or(&path)
+                        )),
+                        warning => warning,
+                    };
+                    warnings.extend(
+                        element_repository
+                            .apply_transformations(mappings.clone())?
+                            .into_iter()
+                            .map(in_element),
+                    );
+
+                    values.push(element_repository.remove(&destination.format).unwrap_or(Value::Null));
                 }
 
-                merge(destination_credential, leaf_node);
+                self.write_destination(destination, Value::Array(values))?;
+                Ok(warnings)
             }
+            // All mappings of the branch are applied, with the warnings of all of them
+            Transformation::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let branch = if self.evaluate(&condition)? { then } else { otherwise };
 
-            _ => todo!(),
+                self.apply_transformations(branch)
+            }
         }
     }
 
-    pub fn apply_transformations(&mut self, transformations: Vec<Transformation>) {
+    /// Checks whether the condition holds for the credentials in the repository. Only the values in the credential are
+    /// checked, the default and `skip_if_missing` of the source are for mappings.
+    pub fn evaluate(&self, condition: &Condition) -> Result<bool, MappingError> {
+        let source = condition.source();
+        let Some((path, matches)) = self.find_first_matches(source)? else {
+            return Ok(false);
+        };
+
+        let value = || combine_matches(path, matches.clone(), &source.matches);
+
+        match condition {
+            Condition::exists { .. } => Ok(!value()?.is_null()),
+            Condition::equals { value: expected, .. } => Ok(value()? == *expected),
+            Condition::regex { pattern, .. } => {
+                let regex = Regex::new(pattern).map_err(|e| MappingError::InvalidTransformation(e.to_string()))?;
+
+                match value()? {
+                    Value::String(value) => Ok(regex.is_match(&value)),
+                    value => Ok(regex.is_match(&value.to_string())),
+                }
+            }
+            // The filter in the path has already selected the values, it holds when one of them is not null
+            Condition::filter { .. } => Ok(matches.iter().any(|value| !value.is_null())),
+        }
+    }
+
+    /// Applies the transformations in order. Transformations whose source has no value are skipped and returned as
+    /// warnings, all other errors stop the mapping.
+    pub fn apply_transformations(
+        &mut self,
+        transformations: Vec<Transformation>,
+    ) -> Result<Vec<MappingError>, MappingError> {
+        let mut warnings = vec![];
         for transformation in transformations {
-            self.apply_transformation(transformation)
+            warnings.extend(self.apply_transformation(transformation)?);
+        }
+        Ok(warnings)
+    }
+
+    /// Replaces every `{{path}}` placeholder with the value at the JSONPath in the credential of the format. Strings are
+    /// inserted without quotes, all other values as JSON.
+    fn fill_template(&self, template: &str, format: &str) -> Result<String, MappingError> {
+        let placeholder = Regex::new(r"\{\{\s*(.*?)\s*\}\}").unwrap();
+
+        let mut filled = String::new();
+        let mut end = 0;
+        for captures in placeholder.captures_iter(template) {
+            let (whole, path) = (captures.get(0).unwrap(), &captures[1]);
+
+            let value = self
+                .find_source_value(&DataLocation {
+                    format: format.to_string(),
+                    path: path.to_string(),
+                    ..Default::default()
+                })?
+                .unwrap_or_default();
+
+            filled.push_str(&template[end..whole.start()]);
+            match value {
+                Value::String(value) => filled.push_str(&value),
+                value => filled.push_str(&value.to_string()),
+            }
+            end = whole.end();
+        }
+        filled.push_str(&template[end..]);
+
+        Ok(filled)
+    }
+
+    /// Returns the value the JSONPath of the data location points to, or else the value of the first fallback that has
+    /// one, or else the default. Multiple matches are combined as the data location prescribes. Returns `None` when
+    /// there is no value and the data location may be skipped.
+    pub fn find_source_value(&self, source: &DataLocation) -> Result<Option<Value>, MappingError> {
+        if let Some((path, matches)) = self.find_first_matches(source)? {
+            return combine_matches(path, matches, &source.matches).map(Some);
+        }
+
+        match &source.default {
+            Some(default) => Ok(Some(default.clone())),
+            None if source.skip_if_missing => Ok(None),
+            None => Err(MappingError::PathNotFound(source.path.clone())),
+        }
+    }
+
+    /// Returns the first path of the data location and its fallbacks that matches a value, with all values it matches.
+    fn find_first_matches<'a>(&self, source: &'a DataLocation) -> Result<Option<(&'a str, Vec<Value>)>, MappingError> {
+        for path in std::iter::once(&source.path).chain(&source.fallbacks) {
+            let matches = self.find_matches(&source.format, path)?;
+            if !matches.is_empty() {
+                return Ok(Some((path, matches)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns all values the JSONPath matches in the credential of the format, in document order.
+    fn find_matches(&self, format: &str, path: &str) -> Result<Vec<Value>, MappingError> {
+        let source_credential = self
+            .get(format)
+            .ok_or_else(|| MappingError::UnknownFormat(format.to_string()))?;
+
+        // Paths to a single value are resolved as a JSON Pointer, so keys with any character can be used
+        if let Ok(pointer) = JsonPointer::try_from(JsonPath(path.to_string())) {
+            return Ok(source_credential.pointer(&pointer).cloned().into_iter().collect());
         }
+
+        let finder = JsonPathFinder::from_str(&source_credential.to_string(), path)
+            .map_err(|_| MappingError::InvalidPointer(path.to_string()))?;
+
+        // The finder returns null instead of an empty array when nothing matches
+        match finder.find() {
+            Value::Array(values) => Ok(values),
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Writes the value to the JSONPath of the data location, creating the credential and all parent objects when
+    /// needed.
+    fn write_destination(&mut self, destination: DataLocation, value: Value) -> Result<(), MappingError> {
+        let pointer = JsonPointer::try_from(JsonPath(destination.path))?;
+
+        let destination_credential = self.entry(destination.format).or_insert(json!({}));
+        insert_at_pointer(destination_credential, &pointer, value)
+    }
+}
+
+/// Combines the values a path matches with the strategy, there has to be at least one match.
+fn combine_matches(path: &str, mut matches: Vec<Value>, strategy: &Matches) -> Result<Value, MappingError> {
+    match strategy {
+        Matches::first => Ok(matches.swap_remove(0)),
+        Matches::last => Ok(matches.pop().unwrap()),
+        Matches::all => Ok(Value::Array(matches)),
+        Matches::join { separator } => Ok(Value::String(
+            matches
+                .into_iter()
+                .map(|value| match value {
+                    Value::String(value) => value,
+                    value => value.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(separator),
+        )),
+        Matches::single if matches.len() > 1 => Err(MappingError::TooManyMatches {
+            path: path.to_string(),
+            matches: matches.len(),
+        }),
+        Matches::single => Ok(matches.swap_remove(0)),
     }
 }
 
-pub fn construct_leaf_node(path: &str) -> Value {
-    // Split the input string by '/' and filter out any empty parts
-    let parts: Vec<&str> = path.split('/').filter(|&s| !s.is_empty()).collect();
+pub fn construct_leaf_node(path: &str) -> Result<Value, MappingError> {
+    // Split the pointer into its unescaped keys, an empty key is a valid key as well
+    let parts = JsonPointer(path.to_string()).tokens();
 
     // Initialize the root of the JSON structure as null
     let mut current_value = Value::Null;
 
-    // Iterate through the parts in reverse order to build the nested structure
+    // Iterate through the parts in reverse order to build the nested structure. Array indices create an array in which
+    // the preceding elements are null.
     for part in parts.into_iter().rev() {
-        let mut new_object = Map::new();
-        new_object.insert(part.to_string(), current_value);
-        current_value = Value::Object(new_object);
+        current_value = if is_array_index(&part) {
+            let index = array_index(&part, path)?;
+            let mut new_array = vec![Value::Null; index + 1];
+            new_array[index] = current_value;
+            Value::Array(new_array)
+        } else {
+            let mut new_object = Map::new();
+            new_object.insert(part, current_value);
+            Value::Object(new_object)
+        };
     }
 
-    current_value
+    Ok(current_value)
+}
+
+/// Parses an array index of the pointer, indices above `MAX_ARRAY_INDEX` are rejected.
+fn array_index(token: &str, pointer: &str) -> Result<usize, MappingError> {
+    token
+        .parse::<usize>()
+        .ok()
+        .filter(|index| is_array_index(token) && *index <= MAX_ARRAY_INDEX)
+        .ok_or_else(|| MappingError::InvalidPointer(pointer.to_string()))
 }
 
 pub fn merge(a: &mut Value, b: Value) {
     match (a, b) {
+        // Null only creates a missing field or array element, it never overwrites a value
+        (_, Value::Null) => {}
         (a @ &mut Value::Object(_), Value::Object(b)) => {
             let a = a.as_object_mut().unwrap();
             for (k, v) in b {
                 merge(a.entry(k).or_insert(Value::Null), v); //
             }
         }
+        (a @ &mut Value::Array(_), Value::Array(b)) => {
+            let a = a.as_array_mut().unwrap();
+            for (index, v) in b.into_iter().enumerate() {
+                match a.get_mut(index) {
+                    Some(a) => merge(a, v),
+                    None => a.push(v),
+                }
+            }
+        }
         (a, b) => *a = b,
     }
 }
 
-pub fn update_repository(state: &mut AppState) {
+/// Writes the value at the pointer, creating all missing parent objects and array elements. An existing value at the
+/// pointer is replaced. A number is a key in an existing object and an index in an existing or a new array.
+pub fn insert_at_pointer(credential: &mut Value, pointer: &str, value: Value) -> Result<(), MappingError> {
+    let mut current = credential;
+
+    for token in JsonPointer(pointer.to_string()).tokens() {
+        if !current.is_object() && !current.is_array() {
+            *current = if is_array_index(&token) {
+                Value::Array(vec![])
+            } else {
+                Value::Object(Map::new())
+            };
+        }
+
+        current = match current {
+            Value::Object(object) => object.entry(token).or_insert(Value::Null),
+            Value::Array(array) => {
+                let index = array_index(&token, pointer)?;
+                if index >= array.len() {
+                    array.resize(index + 1, Value::Null);
+                }
+                &mut array[index]
+            }
+            _ => unreachable!("the parent is an object or an array"),
+        };
+    }
+
+    *current = value;
+    Ok(())
+}
+
+pub fn update_repository(state: &mut AppState) -> Result<(), MappingError> {
     let output_format = state.mapping.output_format();
 
     // let (_, source_value) = state.input_fields[state.selected_input_field].clone();
 
-    let source_value = state.candidate_data_value.clone().unwrap();
-
-    trace_dbg!(state.selected_missing_field);
-    if state.selected_missing_field == 0 {
-        return;
+    trace_dbg!(state.selected_output_field());
+    if state.selected_output_field() == 0 {
+        return Ok(());
     }
 
-    let pointer = state.missing_data_fields[state.selected_missing_field].0.clone();
+    let pointer = state.output_fields()[state.selected_output_field()].0.clone();
     trace_dbg!(&pointer);
 
-    let json_value = state.repository.get_mut(&output_format).unwrap();
+    let source_value = state
+        .candidate_data_value
+        .clone()
+        .ok_or_else(|| MappingError::PathNotFound(pointer.clone()))?;
+
+    let json_value = state
+        .repository
+        .get_mut(&output_format)
+        .ok_or(MappingError::UnknownFormat(output_format))?;
 
-    let mut leaf_node = construct_leaf_node(&pointer);
+    let value = serde_json::from_str(&source_value).map_err(|e| MappingError::TypeMismatch {
+        pointer: pointer.clone(),
+        message: e.to_string(),
+    })?;
 
-    if let Some(value) = leaf_node.pointer_mut(&pointer) {
-        *value = serde_json::from_str(&source_value).unwrap();
+    insert_at_pointer(json_value, &pointer, value)?;
+    trace_dbg!(json_value);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repository() -> Repository {
+        Repository::from(HashMap::from_iter([(
+            "ELM".to_string(),
+            json!({ "levels": [{ "eqf": 5 }, { "eqf": 7 }], "title": "Rust" }),
+        )]))
     }
 
-    trace_dbg!(&leaf_node);
+    fn source(path: &str) -> DataLocation {
+        DataLocation {
+            format: "ELM".to_string(),
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
 
-    merge(json_value, leaf_node);
-    trace_dbg!(json_value);
+    fn copy(from: &str, to: &str) -> Transformation {
+        Transformation::OneToOne {
+            type_: crate::backend::transformations::OneToOne::copy,
+            source: source(from),
+            destination: DataLocation {
+                format: "OBv3".to_string(),
+                path: to.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn filters_hold_when_they_match_a_value() {
+        let repository = repository();
+        let filter = |path: &str| Condition::filter { source: source(path) };
+
+        assert!(repository.evaluate(&filter("$.levels[?(@.eqf >= 6)]")).unwrap());
+        assert!(!repository.evaluate(&filter("$.levels[?(@.eqf >= 8)]")).unwrap());
+    }
+
+    #[test]
+    fn conditions_ignore_the_default() {
+        let repository = repository();
+        let missing = DataLocation {
+            default: Some(json!("Rust")),
+            skip_if_missing: true,
+            ..source("$.name")
+        };
+
+        assert!(!repository
+            .evaluate(&Condition::exists {
+                source: missing.clone()
+            })
+            .unwrap());
+        assert!(!repository
+            .evaluate(&Condition::equals {
+                source: missing,
+                value: json!("Rust")
+            })
+            .unwrap());
+        assert!(repository
+            .evaluate(&Condition::equals {
+                source: source("$.title"),
+                value: json!("Rust")
+            })
+            .unwrap());
+    }
+
+    #[test]
+    fn missing_sources_in_a_branch_are_warnings() {
+        let mut collected = repository();
+        let conditional = Transformation::Conditional {
+            condition: Condition::exists {
+                source: source("$.title"),
+            },
+            then: vec![
+                copy("$.name", "$.name"),
+                copy("$.title", "$.title"),
+                copy("$.id", "$.id"),
+            ],
+            otherwise: vec![],
+        };
+
+        let warnings = collected.apply_transformations(vec![conditional.clone()]).unwrap();
+        assert_eq!(
+            warnings,
+            [
+                MappingError::PathNotFound("$.name".to_string()),
+                MappingError::PathNotFound("$.id".to_string())
+            ]
+        );
+        assert_eq!(collected["OBv3"], json!({ "title": "Rust" }));
+
+        // Applied on its own, the warnings of the whole branch are returned as well
+        let mut repository = repository();
+        assert_eq!(repository.apply_transformation(conditional), Ok(warnings));
+        assert_eq!(repository["OBv3"], json!({ "title": "Rust" }));
+    }
+
+    #[test]
+    fn missing_sources_in_elements_are_warnings_with_their_element() {
+        let mut repository = repository();
+        let for_each = Transformation::ForEach {
+            source: source("$.levels"),
+            destination: DataLocation {
+                format: "OBv3".to_string(),
+                path: "$.levels".to_string(),
+                ..Default::default()
+            },
+            mappings: vec![copy("$.eqf", "$.eqf"), copy("$.name", "$.name")],
+        };
+
+        assert_eq!(
+            repository.apply_transformations(vec![for_each]),
+            Ok(vec![
+                MappingError::PathNotFound("$.levels[0].name".to_string()),
+                MappingError::PathNotFound("$.levels[1].name".to_string())
+            ])
+        );
+        assert_eq!(repository["OBv3"], json!({ "levels": [{ "eqf": 5 }, { "eqf": 7 }] }));
+    }
+
+    #[test]
+    fn inserting_creates_missing_objects_and_arrays() {
+        let mut credential = Value::Null;
+        insert_at_pointer(&mut credential, "/credentialSubject/achievement/1/name", json!("Rust")).unwrap();
+        assert_eq!(
+            credential,
+            json!({ "credentialSubject": { "achievement": [null, { "name": "Rust" }] } })
+        );
+
+        insert_at_pointer(&mut credential, "/credentialSubject/achievement/0/name", json!("Go")).unwrap();
+        assert_eq!(
+            credential.pointer("/credentialSubject/achievement/0/name"),
+            Some(&json!("Go"))
+        );
+        assert_eq!(
+            credential.pointer("/credentialSubject/achievement/1/name"),
+            Some(&json!("Rust"))
+        );
+    }
+
+    #[test]
+    fn numbers_are_keys_of_existing_objects() {
+        let mut credential = json!({ "grades": { "1": "A" } });
+        insert_at_pointer(&mut credential, "/grades/2", json!("B")).unwrap();
+        assert_eq!(credential, json!({ "grades": { "1": "A", "2": "B" } }));
+    }
+
+    #[test]
+    fn numbers_with_leading_zeros_are_keys() {
+        let mut credential = Value::Null;
+        insert_at_pointer(&mut credential, "/codes/01", json!("x")).unwrap();
+        assert_eq!(credential, json!({ "codes": { "01": "x" } }));
+        assert_eq!(
+            construct_leaf_node("/codes/01").unwrap(),
+            json!({ "codes": { "01": null } })
+        );
+    }
+
+    #[test]
+    fn large_array_indices_are_rejected() {
+        let pointer = format!("/items/{}", MAX_ARRAY_INDEX + 1);
+        assert_eq!(
+            insert_at_pointer(&mut Value::Null, &pointer, json!(1)),
+            Err(MappingError::InvalidPointer(pointer.clone()))
+        );
+        assert_eq!(
+            construct_leaf_node(&pointer),
+            Err(MappingError::InvalidPointer(pointer))
+        );
+        assert!(insert_at_pointer(&mut json!({ "items": [] }), "/items/99999999999", json!(1)).is_err());
+    }
 }
@@ -1,76 +1,401 @@
+use regex::Regex;
+use serde_json::Value;
+
 use crate::{
     backend::{
+        error::MappingError,
         jsonpointer::{JsonPath, JsonPointer},
         repository::Repository,
-        transformations::{DataLocation, OneToOne, Transformation},
+        transformations::{Condition, DataLocation, ManyToOne, OneToMany, OneToOne, Transformation},
     },
-    state::{AppState, Transformations},
+    state::{translate, AppState, Combiners, MappingOptions, Transformations},
     trace_dbg,
 };
 
-pub fn selector(state: &mut AppState) {
-    let selected_transformations = [
-        vec![Transformations::DirectCopy],
-        state.selected_transformations.clone(),
-    ]
-    .concat();
-    // This function shows the outcome of the transformation selected in the togglebar not the outcome of the Vec of selected transformations: state.selected_transformations.
-    for transformation in selected_transformations.iter() {
-        trace_dbg!(transformation);
-
-        let (input_format, output_format) = (state.mapping.input_format(), state.mapping.output_format());
-
-        let (source_pointer, _source_value) = state.input_fields[state.selected_input_field].clone();
-
-        let pointer = state.missing_data_fields[state.selected_missing_field].0.clone();
-        let destination_path: JsonPath = JsonPointer(pointer.clone()).into();
-
-        let mut temp_repository = Repository::from(state.repository.clone());
-
-        let transformation = match transformation {
-            Transformations::LowerCase => Transformation::OneToOne {
-                type_: OneToOne::toLowerCase,
-                source: DataLocation {
-                    format: input_format.clone(),
-                    path: JsonPath::from(JsonPointer(source_pointer)).to_string(),
-                },
-                destination: DataLocation {
-                    format: output_format.clone(),
-                    path: destination_path.to_string(),
-                },
-            },
-            Transformations::UpperCase => Transformation::OneToOne {
-                type_: OneToOne::toUpperCase,
-                source: DataLocation {
-                    format: input_format.clone(),
-                    path: JsonPath::from(JsonPointer(source_pointer)).to_string(),
-                },
-                destination: DataLocation {
-                    format: output_format.clone(),
-                    path: destination_path.to_string(),
-                },
-            },
-            // todo: This clippy warning is known, this body is for 'DirectCopy' and all others until they
-            // get their own branches
-            Transformations::DirectCopy | _ => Transformation::OneToOne {
-                type_: OneToOne::copy,
-                source: DataLocation {
-                    format: input_format.clone(),
-                    path: JsonPath::from(JsonPointer(source_pointer)).to_string(),
-                },
-                destination: DataLocation {
-                    format: output_format.clone(),
-                    path: destination_path.to_string(),
-                },
-            },
-        };
+pub fn selector(state: &mut AppState) -> Result<(), MappingError> {
+    state.candidate_steps.clear();
+
+    if state.mapping_option == MappingOptions::OneToMany && !state.select_mapping_option {
+        return one_to_many_selector(state);
+    }
+    if state.mapping_option == MappingOptions::ManyToOne && !state.select_mapping_option {
+        return many_to_one_selector(state);
+    }
+    if state.mapping_option == MappingOptions::Constant && !state.select_mapping_option {
+        return constant_selector(state);
+    }
+
+    let output_format = state.mapping.output_format();
+    let pointer = state.output_fields()[state.selected_output_field()].0.clone();
+    let transformation = pipeline_transformation(state);
+    trace_dbg!(&transformation);
+
+    let mut temp_repository = Repository::from(state.repository.clone());
+    if let Transformation::Conditional { condition, .. } = &transformation {
+        if !temp_repository.evaluate(condition)? {
+            return Err(MappingError::InvalidTransformation(format!(
+                "the condition on `{}` is not met",
+                condition.source().path
+            )));
+        }
+    }
+    temp_repository.apply_transformation(transformation.clone())?;
+
+    let candidate_data_value = temp_repository
+        .get(&output_format)
+        .ok_or_else(|| MappingError::UnknownFormat(output_format.clone()))?
+        .pointer(&pointer)
+        .ok_or_else(|| MappingError::PathNotFound(pointer.clone()))?;
+
+    state.candidate_data_value = Some(candidate_data_value.to_string());
+
+    // Show the result after every step of a pipeline, by applying the pipeline up to and including that step
+    let steps = match &transformation {
+        Transformation::Conditional { then, .. } => then.first(),
+        transformation => Some(transformation),
+    };
+    if let Some(Transformation::Pipeline {
+        pipeline,
+        source,
+        destination,
+    }) = steps
+    {
+        for (index, transformation) in selected_steps(state).into_iter().enumerate() {
+            let mut temp_repository = Repository::from(state.repository.clone());
+            temp_repository.apply_transformation(Transformation::Pipeline {
+                pipeline: pipeline[..=index].to_vec(),
+                source: source.clone(),
+                destination: destination.clone(),
+            })?;
+
+            let value = temp_repository
+                .get(&output_format)
+                .and_then(|output_credential| output_credential.pointer(&pointer))
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            let name = translate(transformation.to_string().to_lowercase().as_str()).to_string();
+            state.candidate_steps.push(format!("[{}] {name}: {value}", index + 1));
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds the transformation from the selected input field to the selected missing field. A single transformation is
+/// stored as is, multiple transformations are stored as a pipeline in the order in which they were selected. With a
+/// condition, the transformation is only applied when the condition holds.
+pub fn pipeline_transformation(state: &AppState) -> Transformation {
+    let (input_format, output_format) = (state.mapping.input_format(), state.mapping.output_format());
+    let (source_pointer, _source_value) = state.input_fields[state.selected_input_field].clone();
+    let pointer = state.output_fields()[state.selected_output_field()].0.clone();
+
+    let source = DataLocation {
+        format: input_format,
+        path: input_path(state, source_pointer),
+        ..Default::default()
+    };
+    let destination = DataLocation {
+        format: output_format,
+        path: output_path(state, pointer),
+        ..Default::default()
+    };
+
+    let transformation = match selected_steps(state).as_slice() {
+        [] => Transformation::OneToOne {
+            type_: OneToOne::copy,
+            source,
+            destination,
+        },
+        [transformation] => Transformation::OneToOne {
+            type_: one_to_one_type(state, *transformation),
+            source,
+            destination,
+        },
+        transformations => Transformation::Pipeline {
+            pipeline: transformations
+                .iter()
+                .map(|&transformation| one_to_one_type(state, transformation))
+                .collect(),
+            source,
+            destination,
+        },
+    };
 
-        temp_repository.apply_transformation(transformation.clone());
-        state.mappings.push(transformation);
+    if !state.selected_transformations.contains(&Transformations::Condition) {
+        return transformation;
+    }
+    Transformation::Conditional {
+        condition: condition(state),
+        then: vec![transformation],
+        otherwise: vec![],
+    }
+}
+
+/// Returns the selected transformations that are steps of the pipeline, which are all but the condition.
+fn selected_steps(state: &AppState) -> Vec<Transformations> {
+    state
+        .selected_transformations
+        .iter()
+        .copied()
+        .filter(|&transformation| transformation != Transformations::Condition)
+        .collect()
+}
 
-        //trace_dbg!(&pointer);
-        let candidate_data_value = temp_repository.get(&output_format).unwrap().pointer(&pointer).unwrap();
+/// Builds the condition from the condition input, on the selected input field unless another path is entered. An empty
+/// value checks that the path has a value, or that a path with a filter like `[?(@.eqf >= 6)]` matches. A value
+/// starting with `~` is a regex, any other value has to be equal, as JSON when it is valid JSON and as text otherwise.
+fn condition(state: &AppState) -> Condition {
+    let (path, value) = (state.condition_input.0.trim(), state.condition_input.1.as_str());
+    let (source_pointer, _source_value) = state.input_fields[state.selected_input_field].clone();
 
-        state.candidate_data_value = Some(candidate_data_value.to_string());
+    let source = DataLocation {
+        format: state.mapping.input_format(),
+        path: if path.is_empty() {
+            input_path(state, source_pointer)
+        } else {
+            path.to_string()
+        },
+        ..Default::default()
+    };
+
+    if value.is_empty() && path.contains("?(") {
+        Condition::filter { source }
+    } else if value.is_empty() {
+        Condition::exists { source }
+    } else if let Some(pattern) = value.strip_prefix('~') {
+        Condition::regex {
+            source,
+            pattern: pattern.to_string(),
+        }
+    } else {
+        Condition::equals {
+            source,
+            value: serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string())),
+        }
     }
 }
+
+fn one_to_one_type(state: &AppState, transformation: Transformations) -> OneToOne {
+    match transformation {
+        Transformations::LowerCase => OneToOne::toLowerCase,
+        Transformations::UpperCase => OneToOne::toUpperCase,
+        Transformations::Slice => slice_type(state),
+        Transformations::Regex => regex_type(state),
+        Transformations::Trim => OneToOne::trim,
+        Transformations::DateTime => OneToOne::toDateTime {
+            timezone: state.timezone_input.trim().to_string(),
+        },
+        Transformations::Lookup => OneToOne::lookup {
+            table: state.lookup_input.0.trim().to_string(),
+            default: (!state.lookup_input.1.is_empty()).then(|| Value::String(state.lookup_input.1.clone())),
+            reverse: false,
+            loaded: None,
+        },
+        Transformations::Text => OneToOne::toString,
+        Transformations::Number => OneToOne::toNumber,
+        Transformations::Boolean => OneToOne::toBoolean,
+        Transformations::Wrap => OneToOne::wrapInArray,
+        Transformations::Unwrap => OneToOne::unwrapArray,
+        // The condition is not a step, it is applied around the pipeline
+        Transformations::DirectCopy | Transformations::Condition => OneToOne::copy,
+    }
+}
+
+/// Builds the slice from the slice input. Both inputs are character positions when they are numbers or empty, otherwise
+/// the text between them is kept.
+fn slice_type(state: &AppState) -> OneToOne {
+    let (start, end) = (state.slice_input.0.trim(), state.slice_input.1.trim());
+    let is_position = |input: &str| input.is_empty() || input.parse::<i64>().is_ok();
+
+    if is_position(start) && is_position(end) {
+        OneToOne::slice {
+            start: start.parse().ok(),
+            end: end.parse().ok(),
+        }
+    } else {
+        OneToOne::sliceBetween {
+            start: state.slice_input.0.clone(),
+            end: state.slice_input.1.clone(),
+        }
+    }
+}
+
+/// Builds the regex from the regex input. Without a replacement the first capture group is kept, or the whole match if
+/// the pattern has no groups.
+fn regex_type(state: &AppState) -> OneToOne {
+    let (pattern, replacement) = state.regex_input.clone();
+    let has_groups = Regex::new(&pattern).is_ok_and(|regex| regex.captures_len() > 1);
+
+    OneToOne::regex {
+        pattern,
+        group: usize::from(has_groups),
+        replacement: (!replacement.is_empty()).then_some(replacement),
+    }
+}
+
+/// Shows the parts of the split input value, one line per selected output field.
+fn one_to_many_selector(state: &mut AppState) -> Result<(), MappingError> {
+    let output_format = state.mapping.output_format();
+    let transformation = one_to_many_transformation(state);
+
+    let mut temp_repository = Repository::from(state.repository.clone());
+    temp_repository.apply_transformation(transformation)?;
+
+    let output_credential = temp_repository
+        .get(&output_format)
+        .ok_or(MappingError::UnknownFormat(output_format))?;
+
+    let candidate_data_value = state
+        .selected_output_fields()
+        .iter()
+        .enumerate()
+        .map(|(index, &field)| {
+            let value = output_credential
+                .pointer(&state.output_fields()[field].0)
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            format!("[{}] {value}", index + 1)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    state.candidate_data_value = Some(candidate_data_value);
+
+    Ok(())
+}
+
+/// Builds the split transformation from the selected input field to all selected output fields.
+pub fn one_to_many_transformation(state: &AppState) -> Transformation {
+    let (input_format, output_format) = (state.mapping.input_format(), state.mapping.output_format());
+    let (source_pointer, _source_value) = state.input_fields[state.selected_input_field].clone();
+
+    Transformation::OneToMany {
+        type_: OneToMany::split {
+            dividers: state.dividers.clone(),
+        },
+        source: DataLocation {
+            format: input_format,
+            path: input_path(state, source_pointer),
+            ..Default::default()
+        },
+        destinations: state
+            .selected_output_fields()
+            .iter()
+            .map(|&field| DataLocation {
+                format: output_format.clone(),
+                path: output_path(state, state.output_fields()[field].0.clone()),
+                ..Default::default()
+            })
+            .collect(),
+    }
+}
+
+/// Shows the combined value of all selected input fields.
+fn many_to_one_selector(state: &mut AppState) -> Result<(), MappingError> {
+    let output_format = state.mapping.output_format();
+    let pointer = state.output_fields()[state.selected_output_field()].0.clone();
+    let transformation = many_to_one_transformation(state);
+
+    let mut temp_repository = Repository::from(state.repository.clone());
+    temp_repository.apply_transformation(transformation)?;
+
+    let candidate_data_value = temp_repository
+        .get(&output_format)
+        .ok_or(MappingError::UnknownFormat(output_format))?
+        .pointer(&pointer)
+        .ok_or(MappingError::PathNotFound(pointer))?;
+
+    state.candidate_data_value = Some(candidate_data_value.to_string());
+
+    Ok(())
+}
+
+/// Builds the transformation that combines all selected input fields into the active output field.
+pub fn many_to_one_transformation(state: &AppState) -> Transformation {
+    let (input_format, output_format) = (state.mapping.input_format(), state.mapping.output_format());
+    let pointer = state.output_fields()[state.selected_output_field()].0.clone();
+
+    let type_ = match state.combiner {
+        Combiners::Concat if state.separator.is_empty() => ManyToOne::concat,
+        Combiners::Concat => ManyToOne::join {
+            separator: state.separator.clone(),
+        },
+        Combiners::Array => ManyToOne::toArray,
+    };
+
+    Transformation::ManyToOne {
+        type_,
+        sources: state
+            .selected_input_fields
+            .iter()
+            .map(|&field| DataLocation {
+                format: input_format.clone(),
+                path: input_path(state, state.input_fields[field].0.clone()),
+                ..Default::default()
+            })
+            .collect(),
+        destination: DataLocation {
+            format: output_format,
+            path: output_path(state, pointer),
+            ..Default::default()
+        },
+    }
+}
+
+/// Shows the value that the constant or template writes into the active output field.
+fn constant_selector(state: &mut AppState) -> Result<(), MappingError> {
+    let output_format = state.mapping.output_format();
+    let pointer = state.output_fields()[state.selected_output_field()].0.clone();
+    let transformation = constant_transformation(state);
+
+    let mut temp_repository = Repository::from(state.repository.clone());
+    temp_repository.apply_transformation(transformation)?;
+
+    let candidate_data_value = temp_repository
+        .get(&output_format)
+        .ok_or(MappingError::UnknownFormat(output_format))?
+        .pointer(&pointer)
+        .ok_or(MappingError::PathNotFound(pointer))?;
+
+    state.candidate_data_value = Some(candidate_data_value.to_string());
+
+    Ok(())
+}
+
+/// Builds the transformation that writes the constant input into the active output field. Input with `{{path}}`
+/// placeholders is a template, input that is valid JSON is written as that value and all other input as text.
+pub fn constant_transformation(state: &AppState) -> Transformation {
+    let (input_format, output_format) = (state.mapping.input_format(), state.mapping.output_format());
+    let pointer = state.output_fields()[state.selected_output_field()].0.clone();
+    let destination = DataLocation {
+        format: output_format,
+        path: output_path(state, pointer),
+        ..Default::default()
+    };
+
+    if state.constant_input.contains("{{") {
+        return Transformation::Template {
+            template: state.constant_input.clone(),
+            format: input_format,
+            destination,
+        };
+    }
+
+    Transformation::Constant {
+        value: serde_json::from_str(&state.constant_input)
+            .unwrap_or_else(|_| Value::String(state.constant_input.clone())),
+        destination,
+    }
+}
+
+/// Returns the JSONPath of a pointer into the input credential.
+fn input_path(state: &AppState, pointer: String) -> String {
+    let credential = state.repository.get(&state.mapping.input_format());
+    JsonPath::from_pointer(JsonPointer(pointer), credential.unwrap_or(&Value::Null)).to_string()
+}
+
+/// Returns the JSONPath of a pointer into the output credential.
+fn output_path(state: &AppState, pointer: String) -> String {
+    let credential = state.repository.get(&state.mapping.output_format());
+    JsonPath::from_pointer(JsonPointer(pointer), credential.unwrap_or(&Value::Null)).to_string()
+}
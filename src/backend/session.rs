@@ -0,0 +1,192 @@
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use super::{error::MappingError, logging::get_data_dir, transformations::Transformation};
+use crate::{
+    state::{AppState, Mapping, Pages},
+    trace_dbg,
+};
+
+/// Version of the session file format that is written by this version of the tool.
+pub const SESSION_FILE_VERSION: u64 = 1;
+
+/// Time between the autosaves of a session in progress.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A mapping session in progress, from which the work on P2 to P4 is resumed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Session {
+    pub version: u64,
+    /// RFC 3339 timestamp of when the session was saved.
+    pub saved: String,
+    pub input_path: String,
+    /// The mapping files from the lowest layer to the highest.
+    pub mapping_paths: Vec<String>,
+    pub output_path: String,
+    pub custom_mapping_path: String,
+    pub mapping: Mapping,
+    pub page: Pages,
+    pub mappings: Vec<Transformation>,
+    /// Completed fields as the pointers of the output field and of its input field, so they are found again when the
+    /// files changed since the session was saved.
+    pub completed_missing_fields: Vec<(String, String)>,
+    pub completed_optional_fields: Vec<(String, String)>,
+}
+
+impl From<&AppState> for Session {
+    fn from(state: &AppState) -> Self {
+        Session {
+            version: SESSION_FILE_VERSION,
+            saved: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            input_path: state.input_path.clone(),
+            mapping_paths: state.mapping_paths.clone(),
+            output_path: state.output_path.clone(),
+            custom_mapping_path: state.custom_mapping_path.clone(),
+            mapping: state.mapping,
+            page: state.page,
+            mappings: state.mappings.clone(),
+            completed_missing_fields: completed_pointers(
+                &state.missing_data_fields,
+                &state.completed_missing_fields,
+                &state.input_fields,
+            ),
+            completed_optional_fields: completed_pointers(
+                &state.optional_fields,
+                &state.completed_optional_fields,
+                &state.input_fields,
+            ),
+        }
+    }
+}
+
+/// Returns the pointers of the completed fields and of their input fields.
+fn completed_pointers(
+    fields: &[(String, String)],
+    completed_fields: &[(usize, usize)],
+    input_fields: &[(String, String)],
+) -> Vec<(String, String)> {
+    completed_fields
+        .iter()
+        .filter_map(|&(field, input_field)| {
+            Some((fields.get(field)?.0.clone(), input_fields.get(input_field)?.0.clone()))
+        })
+        .collect()
+}
+
+impl Session {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, MappingError> {
+        let path = path.as_ref();
+        let invalid_file = |message: String| MappingError::InvalidFile {
+            path: path.display().to_string(),
+            message,
+        };
+
+        let text = std::fs::read_to_string(path).map_err(|e| invalid_file(e.to_string()))?;
+        let session: Session = serde_json::from_str(&text).map_err(|e| invalid_file(e.to_string()))?;
+        if session.version > SESSION_FILE_VERSION {
+            return Err(invalid_file(format!(
+                "version {} is not supported, update the tool to read it (supported: {SESSION_FILE_VERSION})",
+                session.version
+            )));
+        }
+
+        Ok(session)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), MappingError> {
+        let path = path.as_ref();
+        let invalid_file = |message: String| MappingError::InvalidFile {
+            path: path.display().to_string(),
+            message,
+        };
+
+        let json = serde_json::to_string_pretty(self).map_err(|e| invalid_file(e.to_string()))?;
+        std::fs::write(path, json).map_err(|e| invalid_file(e.to_string()))
+    }
+}
+
+/// Where the session in progress is autosaved, it is offered for recovery on the next start while it exists.
+pub fn autosave_path() -> PathBuf {
+    get_data_dir().join("autosave_session.json")
+}
+
+/// Saves the session in progress when the last autosave is long enough ago. Failures are only logged, so they don't
+/// interrupt the mapping.
+pub fn autosave(state: &mut AppState) {
+    if !matches!(
+        state.page,
+        Pages::ManualMappingP2 | Pages::UnusedDataP3 | Pages::MappingRulesP4
+    ) {
+        return;
+    }
+    if state
+        .autosaved
+        .is_some_and(|autosaved| autosaved.elapsed() < AUTOSAVE_INTERVAL)
+    {
+        return;
+    }
+
+    state.autosaved = Some(Instant::now());
+    let path = autosave_path();
+    let result = std::fs::create_dir_all(get_data_dir())
+        .map_err(|e| MappingError::InvalidFile {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })
+        .and_then(|()| Session::from(&*state).write(&path));
+    if let Err(e) = result {
+        trace_dbg!(&e);
+    }
+}
+
+/// Removes the autosaved session once its files are written, so it isn't offered for recovery anymore.
+pub fn discard_autosave() {
+    let _ = std::fs::remove_file(autosave_path());
+}
+
+/// Whether there are mappings that are not in the session file, because they were made after it was last saved or
+/// resumed.
+pub fn has_unsaved_mappings(state: &AppState) -> bool {
+    !state.mappings.is_empty() && serde_json::to_string(&state.mappings).ok().as_ref() != Some(&state.saved_mappings)
+}
+
+/// Saves the unsaved mappings when the program is left, so they are offered for recovery on the next start. Without
+/// unsaved mappings, an older autosave is removed instead, so leaving on purpose doesn't offer it again.
+pub fn autosave_on_exit(state: &mut AppState) {
+    if has_unsaved_mappings(state) {
+        state.autosaved = None;
+        autosave(state);
+    } else {
+        discard_autosave();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::transformations::DataLocation;
+    use serde_json::json;
+
+    #[test]
+    fn mappings_are_unsaved_until_they_are_in_the_session_file() {
+        let mut state = AppState::default();
+        assert!(!has_unsaved_mappings(&state));
+
+        state.mappings = vec![Transformation::Constant {
+            value: json!("Rust"),
+            destination: DataLocation {
+                format: "OUT".to_string(),
+                path: "$.title".to_string(),
+                ..Default::default()
+            },
+        }];
+        assert!(has_unsaved_mappings(&state));
+
+        state.saved_mappings = serde_json::to_string(&state.mappings).unwrap();
+        assert!(!has_unsaved_mappings(&state));
+    }
+}
@@ -0,0 +1,406 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+};
+
+use super::{
+    error::MappingError,
+    jsonpointer::{normalize, JsonPath, JsonPointer},
+    mapping_file::MappingFile,
+    transformations::{DataLocation, Matches, OneToOne, Transformation},
+};
+
+/// Name of the intermediate format all registered formats are mapped to and from.
+pub const SPINE: &str = "spine";
+
+/// The spine registry the mappings through the spine of page 1 are composed from.
+pub const SPINE_REGISTRY: &str = "res/spine.json";
+
+/// The pivot model: every format only has a mapping into the spine and one out of it, a conversion between two formats
+/// is composed of both.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Spine {
+    pub formats: BTreeMap<String, SpineMappings>,
+}
+
+/// Paths of the mapping files of a format, relative to the registry.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpineMappings {
+    pub to_spine: String,
+    pub from_spine: String,
+}
+
+impl Spine {
+    /// Returns the mapping from the input format to the output format, which maps the input format into the spine and
+    /// the spine into the output format. The mapping files are looked up relative to the `registry` file, the paths of
+    /// their lookup tables are made absolute, so the composed mapping can be written anywhere.
+    pub fn compose(
+        &self,
+        registry: &Path,
+        input_format: &str,
+        output_format: &str,
+    ) -> Result<Vec<Transformation>, MappingError> {
+        let base = registry.parent().unwrap_or(Path::new(""));
+        let mappings = |format: &str| {
+            self.formats
+                .get(format)
+                .ok_or_else(|| MappingError::UnknownFormat(format.to_string()))
+        };
+
+        let read = |path: &str| {
+            let path = base.join(path);
+            let mut mapping_file = MappingFile::read(&path)?;
+            let directory = path.parent().unwrap_or(Path::new(""));
+            for mapping in &mut mapping_file.mappings {
+                mapping.rebase_tables(directory);
+            }
+            mapping_file.load_tables(&path)?;
+            Ok::<_, MappingError>(mapping_file.mappings)
+        };
+
+        let (to_spine, from_spine) = (&mappings(input_format)?.to_spine, &mappings(output_format)?.from_spine);
+        let mut transformations = read(to_spine)?;
+        transformations.extend(read(from_spine)?);
+
+        Ok(transformations)
+    }
+}
+
+/// How a transformation produces the value at a spine path.
+enum Produced {
+    /// The value at the source, transformed by the steps.
+    Source(DataLocation, Vec<OneToOne>),
+    Value(Value),
+}
+
+/// Rewrites the transformations that read from the spine into transformations that read from the sources of those
+/// spine values directly. Returns the flattened transformations and the spine paths that could not be flattened. The
+/// transformations that read these paths are kept together with all transformations that write these paths, their
+/// parents or their children, so they still find the values they read in the spine.
+pub fn flatten(transformations: Vec<Transformation>) -> (Vec<Transformation>, Vec<String>) {
+    // The transformations that write the spine, in order, and the last one to write every spine path
+    let mut producers: Vec<Transformation> = vec![];
+    let mut producer_of: HashMap<String, usize> = HashMap::new();
+
+    let mut flattened = vec![];
+    let mut unflattened = BTreeSet::new();
+    let mut needed = BTreeSet::new();
+
+    for transformation in transformations {
+        let reads_spine = transformation.sources().iter().any(|source| source.format == SPINE)
+            || matches!(&transformation, Transformation::Template { format, .. } if format == SPINE);
+
+        let transformation = if !reads_spine {
+            transformation
+        } else if let Some(substituted) = substitute(&transformation, &producers, &producer_of) {
+            substituted
+        } else {
+            // Keep the transformation as is, with the transformations that write the spine paths it reads
+            for source in transformation
+                .sources()
+                .into_iter()
+                .filter(|source| source.format == SPINE)
+            {
+                unflattened.insert(source.path.clone());
+                needed.extend(producers_of(&producer_of, &source.path));
+            }
+            if let Transformation::Template { template, .. } = &transformation {
+                unflattened.insert(template.clone());
+                needed.extend(producer_of.values().copied());
+            }
+            transformation
+        };
+
+        let writes_spine = transformation
+            .destinations()
+            .iter()
+            .any(|destination| destination.format == SPINE);
+        if writes_spine {
+            for destination in transformation.destinations() {
+                producer_of.insert(normalize(&destination.path), producers.len());
+            }
+            producers.push(transformation);
+        } else {
+            flattened.push(transformation);
+        }
+    }
+
+    // The kept transformations that write the spine run first, in their original order
+    let mut kept: Vec<Transformation> = producers
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| needed.contains(index))
+        .map(|(_, producer)| producer)
+        .collect();
+    kept.extend(flattened);
+
+    (kept, unflattened.into_iter().collect())
+}
+
+/// Returns the transformation with all spine sources replaced by the sources of their values, or `None` when that is
+/// not possible.
+fn substitute(
+    transformation: &Transformation,
+    producers: &[Transformation],
+    producer_of: &HashMap<String, usize>,
+) -> Option<Transformation> {
+    let produced = |source: &DataLocation| {
+        if source.format != SPINE {
+            return Some(Produced::Source(source.clone(), vec![]));
+        }
+        // Only a value that a single transformation wrote at exactly this path can be substituted
+        let index = *producer_of.get(&normalize(&source.path))?;
+        if !is_plain(source) || producers_of(producer_of, &source.path) != BTreeSet::from([index]) {
+            return None;
+        }
+        produced_by(&producers[index])
+    };
+    // Sources of transformations that don't transform the value themselves can only be replaced by copied values
+    let copied = |source: &DataLocation| match produced(source)? {
+        Produced::Source(source, steps) if steps.iter().all(|step| matches!(step, OneToOne::copy)) => Some(source),
+        _ => None,
+    };
+
+    match transformation.clone() {
+        Transformation::OneToOne {
+            type_,
+            source,
+            destination,
+        } => with_steps(produced(&source)?, vec![type_], destination),
+        Transformation::Pipeline {
+            pipeline,
+            source,
+            destination,
+        } => with_steps(produced(&source)?, pipeline, destination),
+        Transformation::OneToMany {
+            type_,
+            source,
+            destinations,
+        } => Some(Transformation::OneToMany {
+            type_,
+            source: copied(&source)?,
+            destinations,
+        }),
+        Transformation::ManyToOne {
+            type_,
+            sources,
+            destination,
+        } => Some(Transformation::ManyToOne {
+            type_,
+            sources: sources.iter().map(copied).collect::<Option<_>>()?,
+            destination,
+        }),
+        // The mappings of the elements and the placeholders of templates refer to the spine as well
+        _ => None,
+    }
+}
+
+/// Returns the producers that write the spine path, one of its parents or one of its children. A path that isn't a
+/// pointer, like a path with a wildcard, can read the values of any producer.
+fn producers_of(producer_of: &HashMap<String, usize>, path: &str) -> BTreeSet<usize> {
+    let Ok(pointer) = JsonPointer::try_from(JsonPath(path.to_string())) else {
+        return producer_of.values().copied().collect();
+    };
+    let within = |path: &str, parent: &str| path.strip_prefix(parent).is_some_and(|rest| rest.starts_with('/'));
+
+    producer_of
+        .iter()
+        .filter(|(written, _)| **written == *pointer || within(&pointer, written) || within(written, &pointer))
+        .map(|(_, &producer)| producer)
+        .collect()
+}
+
+/// Returns how the transformation produces its value, if it writes a single value.
+fn produced_by(transformation: &Transformation) -> Option<Produced> {
+    match transformation.clone() {
+        Transformation::OneToOne { type_, source, .. } => Some(Produced::Source(source, vec![type_])),
+        Transformation::Pipeline { pipeline, source, .. } => Some(Produced::Source(source, pipeline)),
+        Transformation::Constant { value, .. } => Some(Produced::Value(value)),
+        _ => None,
+    }
+}
+
+/// Builds the transformation that applies the steps to the produced value. Constants are transformed right away.
+fn with_steps(produced: Produced, steps: Vec<OneToOne>, destination: DataLocation) -> Option<Transformation> {
+    match produced {
+        Produced::Value(value) => Some(Transformation::Constant {
+            value: steps.iter().try_fold(value, |value, step| step.apply(value)).ok()?,
+            destination,
+        }),
+        Produced::Source(source, mut pipeline) => {
+            pipeline.extend(steps);
+            pipeline.retain(|step| !matches!(step, OneToOne::copy));
+
+            Some(match pipeline.len() {
+                0 => Transformation::OneToOne {
+                    type_: OneToOne::copy,
+                    source,
+                    destination,
+                },
+                1 => Transformation::OneToOne {
+                    type_: pipeline.remove(0),
+                    source,
+                    destination,
+                },
+                _ => Transformation::Pipeline {
+                    pipeline,
+                    source,
+                    destination,
+                },
+            })
+        }
+    }
+}
+
+/// Whether the data location only has a path, without fallbacks, a default or another strategy for multiple matches.
+fn is_plain(location: &DataLocation) -> bool {
+    location.matches == Matches::first
+        && location.fallbacks.is_empty()
+        && location.default.is_none()
+        && !location.skip_if_missing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn location(format: &str, path: &str) -> DataLocation {
+        DataLocation {
+            format: format.to_string(),
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn mapping(type_: OneToOne, from: (&str, &str), to: (&str, &str)) -> Transformation {
+        Transformation::OneToOne {
+            type_,
+            source: location(from.0, from.1),
+            destination: location(to.0, to.1),
+        }
+    }
+
+    fn json(transformations: &[Transformation]) -> Value {
+        serde_json::to_value(transformations).unwrap()
+    }
+
+    #[test]
+    fn exact_spine_paths_are_substituted() {
+        let (flattened, unflattened) = flatten(vec![
+            mapping(OneToOne::trim, ("ELM", "$.title"), (SPINE, "$.name")),
+            mapping(OneToOne::toUpperCase, (SPINE, "$.name"), ("OBv3", "$.name")),
+        ]);
+
+        assert!(unflattened.is_empty());
+        assert_eq!(
+            json(&flattened),
+            json(&[Transformation::Pipeline {
+                pipeline: vec![OneToOne::trim, OneToOne::toUpperCase],
+                source: location("ELM", "$.title"),
+                destination: location("OBv3", "$.name"),
+            }])
+        );
+    }
+
+    #[test]
+    fn producers_of_parents_and_children_are_kept() {
+        let parent = mapping(OneToOne::copy, ("ELM", "$.issuer"), (SPINE, "$.issuer"));
+        let child = mapping(OneToOne::copy, ("ELM", "$.title"), (SPINE, "$.achievement.name"));
+        let unrelated = mapping(OneToOne::copy, ("ELM", "$.id"), (SPINE, "$.id"));
+        let reads_parent = mapping(OneToOne::copy, (SPINE, "$.issuer.name"), ("OBv3", "$.issuer.name"));
+        let reads_child = mapping(OneToOne::copy, (SPINE, "$.achievement"), ("OBv3", "$.achievement"));
+
+        let (flattened, unflattened) = flatten(vec![
+            parent.clone(),
+            child.clone(),
+            unrelated,
+            reads_parent.clone(),
+            reads_child.clone(),
+        ]);
+
+        assert_eq!(unflattened, ["$.achievement", "$.issuer.name"]);
+        assert_eq!(json(&flattened), json(&[parent, child, reads_parent, reads_child]));
+    }
+
+    #[test]
+    fn wildcards_keep_all_producers() {
+        let producer = mapping(OneToOne::copy, ("ELM", "$.levels"), (SPINE, "$.levels"));
+        let reader = mapping(OneToOne::copy, (SPINE, "$.levels[*].eqf"), ("OBv3", "$.level"));
+
+        let (flattened, unflattened) = flatten(vec![producer.clone(), reader.clone()]);
+
+        assert_eq!(unflattened, ["$.levels[*].eqf"]);
+        assert_eq!(json(&flattened), json(&[producer, reader]));
+    }
+
+    #[test]
+    fn constants_are_transformed_right_away() {
+        let (flattened, _) = flatten(vec![
+            Transformation::Constant {
+                value: json!("rust"),
+                destination: location(SPINE, "$.name"),
+            },
+            mapping(OneToOne::toUpperCase, (SPINE, "$.name"), ("OBv3", "$.name")),
+        ]);
+
+        assert_eq!(
+            json(&flattened),
+            json(&[Transformation::Constant {
+                value: json!("RUST"),
+                destination: location("OBv3", "$.name"),
+            }])
+        );
+    }
+
+    #[test]
+    fn mapping_files_are_relative_to_the_registry() {
+        let directory = std::env::temp_dir().join(format!("impierce-mapper-spine-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("spine")).unwrap();
+        let write = |name: &str, mappings: &[Transformation]| {
+            std::fs::write(directory.join(name), serde_json::to_string(mappings).unwrap()).unwrap();
+        };
+        write(
+            "spine/a.json",
+            &[mapping(OneToOne::copy, ("A", "$.id"), (SPINE, "$.id"))],
+        );
+        let lookup = OneToOne::lookup {
+            table: "ids.json".to_string(),
+            default: None,
+            reverse: false,
+            loaded: None,
+        };
+        write("spine/b.json", &[mapping(lookup, (SPINE, "$.id"), ("B", "$.id"))]);
+        std::fs::write(directory.join("spine/ids.json"), r#"{ "1": "one" }"#).unwrap();
+        let spine: Spine = serde_json::from_value(json!({ "formats": {
+            "A": { "to_spine": "spine/a.json", "from_spine": "spine/a.json" },
+            "B": { "to_spine": "spine/b.json", "from_spine": "spine/b.json" },
+        } }))
+        .unwrap();
+
+        let composed = spine.compose(&directory.join("registry.json"), "A", "B");
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // The lookup table is still found when the composed mapping is written elsewhere
+        let composed = composed.unwrap();
+        assert_eq!(composed.len(), 2);
+        let Transformation::OneToOne {
+            type_: OneToOne::lookup { table, loaded, .. },
+            ..
+        } = &composed[1]
+        else {
+            panic!("{composed:?}");
+        };
+        assert_eq!(
+            Path::new(table),
+            std::path::absolute(directory.join("spine/ids.json")).unwrap()
+        );
+        assert!(loaded.is_some());
+        assert_eq!(
+            spine.compose(Path::new("registry.json"), "A", "C").unwrap_err(),
+            MappingError::UnknownFormat("C".to_string())
+        );
+    }
+}
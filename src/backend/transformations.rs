@@ -1,5 +1,10 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Offset, SecondsFormat, TimeZone, Utc};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::{path::Path, sync::Arc};
+
+use super::{error::MappingError, lookup::LookupTable};
 
 #[allow(non_camel_case_types)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -7,42 +12,303 @@ pub enum OneToOne {
     copy,
     toLowerCase,
     toUpperCase,
-    slice,
+    /// Removes whitespace at the start and the end.
+    trim,
+    /// Keeps the characters from `start` up to, but not including, `end`. Negative positions count from the end of the
+    /// string, a missing position means the start or the end of the string.
+    slice {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start: Option<i64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        end: Option<i64>,
+    },
+    /// Keeps the text between the first `start` and the first `end` after it. An empty `start` or `end` means the start
+    /// or the end of the string.
+    sliceBetween {
+        start: String,
+        end: String,
+    },
+    /// Replaces all matches of `pattern` with `replacement`, which can refer to capture groups like `$1`. Without a
+    /// replacement only capture `group` of the first match is kept, where group 0 is the whole match.
+    regex {
+        pattern: String,
+        #[serde(default)]
+        group: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        replacement: Option<String>,
+    },
+    /// Converts ISO dates and datetimes, dd-mm-yyyy dates and epoch seconds, given as number, into an ISO 8601 datetime,
+    /// as used by xsd:dateTime. The `timezone` is an offset like `+02:00`, UTC when empty. Values without an offset are taken to be
+    /// in this timezone, values with an offset are converted to it.
+    toDateTime {
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        timezone: String,
+    },
+    /// Translates the value with the lookup `table`, a CSV or JSON file. Values that are not in the table translate into
+    /// `default`, or fail when there is no default. A `reverse` table translates its values back into their keys.
+    lookup {
+        table: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        default: Option<Value>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        reverse: bool,
+        /// The table as loaded with the mapping file, tables that are not loaded yet are loaded when they are applied.
+        #[serde(skip)]
+        loaded: Option<Arc<LookupTable>>,
+    },
+    /// Converts numbers and booleans into text, objects and arrays are serialized as JSON.
+    toString,
+    /// Parses text into a number.
+    toNumber,
+    /// Parses `true`, `false`, `yes`, `no`, `1` and `0` into a boolean, as text or as number.
+    toBoolean,
+    /// Wraps a value that is not an array into an array with only that value, like the `type` of a credential.
+    wrapInArray,
+    /// Takes the value out of an array with exactly one element, values that are not arrays are kept as they are.
+    unwrapArray,
 }
 
 impl OneToOne {
-    pub fn apply(&self, value: Value) -> Value {
+    /// Dates, lookups and type conversions accept all values, the other transformations only transform strings and pass
+    /// all other values through unchanged.
+    pub fn apply(&self, value: Value) -> Result<Value, MappingError> {
+        let cannot_convert = |value: &Value, into: &str| {
+            MappingError::InvalidTransformation(format!("{value} cannot be converted into {into}"))
+        };
+
         match self {
-            OneToOne::copy => value,
-            OneToOne::toLowerCase => {
-                if let Value::String(s) = value {
-                    Value::String(s.to_lowercase())
-                } else {
-                    value
+            OneToOne::toDateTime { timezone } => to_date_time(&value, timezone).map(Value::String),
+            OneToOne::lookup {
+                table,
+                default,
+                reverse,
+                loaded,
+            } => match loaded {
+                Some(loaded) => loaded.translate(&value, default.as_ref()),
+                None => LookupTable::load(table, *reverse)?.translate(&value, default.as_ref()),
+            },
+            OneToOne::toString => match value {
+                Value::Null => Err(cannot_convert(&value, "text")),
+                Value::String(s) => Ok(Value::String(s)),
+                value => Ok(Value::String(value.to_string())),
+            },
+            OneToOne::toNumber => match &value {
+                Value::Number(_) => Ok(value),
+                Value::String(s) => {
+                    let s = s.trim();
+                    s.parse::<i64>()
+                        .map(Value::from)
+                        .ok()
+                        .or_else(|| {
+                            s.parse::<f64>()
+                                .ok()
+                                .and_then(|f| serde_json::Number::from_f64(f).map(Value::Number))
+                        })
+                        .ok_or_else(|| cannot_convert(&value, "a number"))
                 }
+                _ => Err(cannot_convert(&value, "a number")),
+            },
+            OneToOne::toBoolean => {
+                let boolean = match &value {
+                    Value::Bool(boolean) => Some(*boolean),
+                    Value::Number(number) if number.as_f64() == Some(1.0) => Some(true),
+                    Value::Number(number) if number.as_f64() == Some(0.0) => Some(false),
+                    Value::String(s) => match s.trim().to_lowercase().as_str() {
+                        "true" | "yes" | "1" => Some(true),
+                        "false" | "no" | "0" => Some(false),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                boolean
+                    .map(Value::Bool)
+                    .ok_or_else(|| cannot_convert(&value, "a boolean"))
             }
-            OneToOne::toUpperCase => {
-                if let Value::String(s) = value {
-                    Value::String(s.to_uppercase())
-                } else {
-                    value
+            OneToOne::wrapInArray => match value {
+                Value::Array(_) => Ok(value),
+                value => Ok(Value::Array(vec![value])),
+            },
+            OneToOne::unwrapArray => match value {
+                Value::Array(mut values) if values.len() == 1 => Ok(values.remove(0)),
+                Value::Array(_) => Err(MappingError::InvalidTransformation(format!(
+                    "{value} does not have exactly one element"
+                ))),
+                value => Ok(value),
+            },
+            _ => match value {
+                Value::String(s) => self.apply_to_string(s).map(Value::String),
+                value => Ok(value),
+            },
+        }
+    }
+
+    /// Loads the lookup table, a relative path is taken relative to the directory `base`.
+    pub fn load_table(&mut self, base: &Path) -> Result<(), MappingError> {
+        if let OneToOne::lookup {
+            table, reverse, loaded, ..
+        } = self
+        {
+            *loaded = Some(Arc::new(LookupTable::load(base.join(table), *reverse)?));
+        }
+        Ok(())
+    }
+
+    /// Makes the path of the lookup table absolute, a relative path is taken relative to the directory `base`. The table
+    /// is then found from wherever the mapping is written to.
+    pub fn rebase_table(&mut self, base: &Path) {
+        if let OneToOne::lookup { table, .. } = self {
+            let path = base.join(&*table);
+            *table = std::path::absolute(&path).unwrap_or(path).display().to_string();
+        }
+    }
+
+    fn apply_to_string(&self, s: String) -> Result<String, MappingError> {
+        let s = match self {
+            OneToOne::copy => s,
+            OneToOne::toLowerCase => s.to_lowercase(),
+            OneToOne::toUpperCase => s.to_uppercase(),
+            OneToOne::trim => s.trim().to_string(),
+            OneToOne::slice { start, end } => slice(&s, *start, *end),
+            OneToOne::sliceBetween { start, end } => slice_between(&s, start, end)?,
+            OneToOne::regex {
+                pattern,
+                group,
+                replacement,
+            } => {
+                let regex = Regex::new(pattern).map_err(|e| MappingError::InvalidTransformation(e.to_string()))?;
+
+                match replacement {
+                    Some(replacement) => regex.replace_all(&s, replacement.as_str()).to_string(),
+                    None => regex
+                        .captures(&s)
+                        .and_then(|captures| captures.get(*group))
+                        .map(|capture| capture.as_str().to_string())
+                        .ok_or_else(|| {
+                            MappingError::InvalidTransformation(format!("no group {group} of `{pattern}` in `{s}`"))
+                        })?,
                 }
             }
-            _ => value,
-        }
+            // Applied to all values in `apply`
+            OneToOne::toDateTime { .. }
+            | OneToOne::lookup { .. }
+            | OneToOne::toString
+            | OneToOne::toNumber
+            | OneToOne::toBoolean
+            | OneToOne::wrapInArray
+            | OneToOne::unwrapArray => s,
+        };
+
+        Ok(s)
     }
 }
 
+fn slice(s: &str, start: Option<i64>, end: Option<i64>) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let len = chars.len() as i64;
+
+    // Negative positions count from the end, positions outside of the string are clamped to it
+    let position = |position: i64| if position < 0 { len + position } else { position }.clamp(0, len) as usize;
+    let start = start.map(position).unwrap_or(0);
+    let end = end.map(position).unwrap_or(chars.len());
+
+    if start >= end {
+        return String::new();
+    }
+    chars[start..end].iter().collect()
+}
+
+fn to_date_time(value: &Value, timezone: &str) -> Result<String, MappingError> {
+    let offset = match timezone {
+        "" | "Z" | "UTC" => Utc.fix(),
+        timezone => timezone
+            .parse::<FixedOffset>()
+            .map_err(|_| MappingError::InvalidTransformation(format!("invalid timezone `{timezone}`")))?,
+    };
+    let invalid_date = || MappingError::InvalidTransformation(format!("{value} is not a supported date"));
+
+    let date_time = match value {
+        Value::Number(seconds) => seconds
+            .as_i64()
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+            .map(|date_time| date_time.with_timezone(&offset)),
+        Value::String(s) => parse_date_time(s.trim(), &offset),
+        _ => None,
+    }
+    .ok_or_else(invalid_date)?;
+
+    Ok(date_time.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+fn parse_date_time(s: &str, offset: &FixedOffset) -> Option<DateTime<FixedOffset>> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(s) {
+        return Some(date_time.with_timezone(offset));
+    }
+
+    let date_time = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .or_else(|| {
+            ["%Y-%m-%d", "%d-%m-%Y", "%d/%m/%Y", "%d.%m.%Y"]
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(s, format).ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+
+    offset.from_local_datetime(&date_time).single()
+}
+
+fn slice_between(s: &str, start: &str, end: &str) -> Result<String, MappingError> {
+    let not_found = |divider: &str| MappingError::InvalidTransformation(format!("`{divider}` not found in `{s}`"));
+
+    let rest = if start.is_empty() {
+        s
+    } else {
+        let index = s.find(start).ok_or_else(|| not_found(start))?;
+        &s[index + start.len()..]
+    };
+
+    if end.is_empty() {
+        return Ok(rest.to_string());
+    }
+    let index = rest.find(end).ok_or_else(|| not_found(end))?;
+    Ok(rest[..index].to_string())
+}
+
 #[allow(non_camel_case_types)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum OneToMany {
-    split,
+    /// Splits a string on every character in `dividers`, empty parts are skipped.
+    split { dividers: String },
+}
+
+impl OneToMany {
+    /// Returns the parts of the value in the order in which they are written to the destinations.
+    pub fn apply(&self, value: Value) -> Vec<Value> {
+        match self {
+            OneToMany::split { dividers } => {
+                if let Value::String(s) = value {
+                    s.split(|c| dividers.contains(c))
+                        .filter(|part| !part.is_empty())
+                        .map(|part| Value::String(part.to_string()))
+                        .collect()
+                } else {
+                    vec![value]
+                }
+            }
+        }
+    }
 }
 
 #[allow(non_camel_case_types)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ManyToOne {
     concat,
+    /// Concatenates the strings with the separator in between.
+    join {
+        separator: String,
+    },
+    /// Collects all values into an array.
+    toArray,
 }
 
 impl ManyToOne {
@@ -57,6 +323,17 @@ impl ManyToOne {
                 }
                 Value::String(s)
             }
+            ManyToOne::join { separator } => Value::String(
+                values
+                    .into_iter()
+                    .filter_map(|value| match value {
+                        Value::String(string) => Some(string),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(separator),
+            ),
+            ManyToOne::toArray => Value::Array(values),
         }
     }
 }
@@ -79,10 +356,270 @@ pub enum Transformation {
         sources: Vec<DataLocation>,
         destination: DataLocation,
     },
+    /// Applies the transformations one after the other, every step transforms the result of the previous step.
+    Pipeline {
+        pipeline: Vec<OneToOne>,
+        source: DataLocation,
+        destination: DataLocation,
+    },
+    /// Writes a literal JSON value, for fields that have no counterpart in the source credential.
+    Constant { value: Value, destination: DataLocation },
+    /// Writes the template as text, with placeholders like `{{$.id}}` replaced by the value at that JSONPath in the
+    /// credential of `format`.
+    Template {
+        template: String,
+        format: String,
+        destination: DataLocation,
+    },
+    /// Applies the `mappings` to every element of the array at `source` and writes their results as the elements of the
+    /// array at `destination`. The paths of the mappings are relative to the element, `$` being the element itself.
+    ForEach {
+        source: DataLocation,
+        destination: DataLocation,
+        mappings: Vec<Transformation>,
+    },
+    /// Applies the `then` mappings when the condition holds, and the `else` mappings otherwise.
+    Conditional {
+        #[serde(rename = "if")]
+        condition: Condition,
+        then: Vec<Transformation>,
+        #[serde(rename = "else", default, skip_serializing_if = "Vec::is_empty")]
+        otherwise: Vec<Transformation>,
+    },
 }
 
+impl Transformation {
+    /// Loads the lookup tables of the transformation and of the mappings in it, relative to the directory `base`.
+    pub fn load_tables(&mut self, base: &Path) -> Result<(), MappingError> {
+        self.try_for_each_step(&mut |step| step.load_table(base))
+    }
+
+    /// Makes the paths of the lookup tables of the transformation and of the mappings in it absolute, relative to the
+    /// directory `base`.
+    pub fn rebase_tables(&mut self, base: &Path) {
+        let _ = self.try_for_each_step(&mut |step| {
+            step.rebase_table(base);
+            Ok(())
+        });
+    }
+
+    /// Calls `f` with every step of the transformation and of the mappings in it, until it fails.
+    fn try_for_each_step(
+        &mut self,
+        f: &mut impl FnMut(&mut OneToOne) -> Result<(), MappingError>,
+    ) -> Result<(), MappingError> {
+        match self {
+            Transformation::OneToOne { type_, .. } => f(type_),
+            Transformation::Pipeline { pipeline, .. } => pipeline.iter_mut().try_for_each(f),
+            Transformation::ForEach { mappings, .. } => {
+                mappings.iter_mut().try_for_each(|mapping| mapping.try_for_each_step(f))
+            }
+            Transformation::Conditional { then, otherwise, .. } => then
+                .iter_mut()
+                .chain(otherwise)
+                .try_for_each(|mapping| mapping.try_for_each_step(f)),
+            Transformation::OneToMany { .. }
+            | Transformation::ManyToOne { .. }
+            | Transformation::Constant { .. }
+            | Transformation::Template { .. } => Ok(()),
+        }
+    }
+
+    /// Returns the data locations the transformation reads from. The mappings of a `ForEach` read from its elements and
+    /// are not included, the branches of a `Conditional` are.
+    pub fn sources(&self) -> Vec<&DataLocation> {
+        match self {
+            Transformation::OneToOne { source, .. }
+            | Transformation::OneToMany { source, .. }
+            | Transformation::Pipeline { source, .. }
+            | Transformation::ForEach { source, .. } => vec![source],
+            Transformation::ManyToOne { sources, .. } => sources.iter().collect(),
+            Transformation::Constant { .. } | Transformation::Template { .. } => vec![],
+            Transformation::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => std::iter::once(condition.source())
+                .chain(then.iter().chain(otherwise).flat_map(Transformation::sources))
+                .collect(),
+        }
+    }
+
+    /// Returns the data locations the transformation writes to.
+    pub fn destinations(&self) -> Vec<&DataLocation> {
+        match self {
+            Transformation::OneToOne { destination, .. }
+            | Transformation::ManyToOne { destination, .. }
+            | Transformation::Pipeline { destination, .. }
+            | Transformation::Constant { destination, .. }
+            | Transformation::Template { destination, .. }
+            | Transformation::ForEach { destination, .. } => vec![destination],
+            Transformation::OneToMany { destinations, .. } => destinations.iter().collect(),
+            Transformation::Conditional { then, otherwise, .. } => then
+                .iter()
+                .chain(otherwise)
+                .flat_map(Transformation::destinations)
+                .collect(),
+        }
+    }
+
+    /// Names the transformation in reports by its number, counting from 1, and its first source and destination.
+    pub fn describe(&self, index: usize) -> String {
+        let path = |locations: Vec<&DataLocation>| {
+            locations
+                .first()
+                .map(|location| format!("{} {}", location.format, location.path))
+                .unwrap_or_else(|| "constant".to_string())
+        };
+
+        format!(
+            "mapping {} ({} -> {})",
+            index + 1,
+            path(self.sources()),
+            path(self.destinations())
+        )
+    }
+}
+
+/// A condition on a value of a credential. A source without a value never satisfies a condition, its `default` is not
+/// used.
+#[allow(non_camel_case_types)]
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Condition {
+    /// The source has a value that is not null.
+    exists { source: DataLocation },
+    /// The value of the source is equal to `value`.
+    equals { source: DataLocation, value: Value },
+    /// The value of the source matches the pattern, values that aren't strings are matched as JSON.
+    regex { source: DataLocation, pattern: String },
+    /// The path of the source, typically with a filter like `$.levels[?(@.eqf >= 6)]`, matches at least one value.
+    filter { source: DataLocation },
+}
+
+impl Condition {
+    pub fn source(&self) -> &DataLocation {
+        match self {
+            Condition::exists { source }
+            | Condition::equals { source, .. }
+            | Condition::regex { source, .. }
+            | Condition::filter { source } => source,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct DataLocation {
     pub format: String,
     pub path: String,
+    /// How the values are combined when the path of a source matches more than one value.
+    #[serde(default, skip_serializing_if = "Matches::is_first")]
+    pub matches: Matches,
+    /// Alternative paths of a source in the same format, tried in order when the path has no value.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallbacks: Vec<String>,
+    /// The value of a source when neither the path nor the fallbacks have a value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    /// Skips the mapping without a warning when the source has no value and no default.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skip_if_missing: bool,
+}
+
+/// Strategies for JSONPath sources like `$.achievements[*].title`, which can match any number of values.
+#[allow(non_camel_case_types)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum Matches {
+    #[default]
+    first,
+    last,
+    /// Collects all matches into an array.
+    all,
+    /// Concatenates the matches as text with the separator in between.
+    join {
+        separator: String,
+    },
+    /// Fails when the path matches more than one value.
+    single,
+}
+
+impl Matches {
+    fn is_first(&self) -> bool {
+        *self == Matches::first
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn date(value: Value, timezone: &str) -> Result<String, MappingError> {
+        OneToOne::toDateTime {
+            timezone: timezone.to_string(),
+        }
+        .apply(value)
+        .map(|value| value.as_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn numbers_are_epoch_seconds() {
+        assert_eq!(date(json!(0), "").unwrap(), "1970-01-01T00:00:00Z");
+        assert_eq!(date(json!(1262304000), "+02:00").unwrap(), "2010-01-01T02:00:00+02:00");
+    }
+
+    #[test]
+    fn numeric_text_is_not_epoch_seconds() {
+        assert!(date(json!("2010"), "").is_err());
+        assert!(date(json!("20100101"), "").is_err());
+        assert!(date(json!("1262304000"), "").is_err());
+    }
+
+    #[test]
+    fn dates_are_midnight_in_the_timezone() {
+        assert_eq!(date(json!("2010-01-31"), "").unwrap(), "2010-01-31T00:00:00Z");
+        assert_eq!(
+            date(json!("31-01-2010"), "+02:00").unwrap(),
+            "2010-01-31T00:00:00+02:00"
+        );
+        assert_eq!(date(json!("31/01/2010"), "UTC").unwrap(), "2010-01-31T00:00:00Z");
+        assert_eq!(date(json!(" 31.01.2010 "), "").unwrap(), "2010-01-31T00:00:00Z");
+    }
+
+    #[test]
+    fn datetimes_with_an_offset_are_converted() {
+        assert_eq!(
+            date(json!("2010-01-31T12:00:00+02:00"), "").unwrap(),
+            "2010-01-31T10:00:00Z"
+        );
+        assert_eq!(
+            date(json!("2010-01-31 12:00:00"), "-05:00").unwrap(),
+            "2010-01-31T12:00:00-05:00"
+        );
+    }
+
+    #[test]
+    fn tables_are_loaded_relative_to_the_base() {
+        let base = std::env::temp_dir().join(format!("impierce-mapper-tables-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("levels.csv"), "6,Bachelor\n").unwrap();
+
+        let mut lookup = OneToOne::lookup {
+            table: "levels.csv".to_string(),
+            default: None,
+            reverse: false,
+            loaded: None,
+        };
+        let loaded = lookup.load_table(&base);
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert!(loaded.is_ok());
+        assert_eq!(lookup.apply(json!("6")).unwrap(), json!("Bachelor"));
+    }
+
+    #[test]
+    fn invalid_dates_and_timezones_fail() {
+        assert!(date(json!("2010-02-30"), "").is_err());
+        assert!(date(json!(true), "").is_err());
+        assert!(date(json!("2010-01-31"), "Europe/Amsterdam").is_err());
+    }
 }
@@ -0,0 +1,333 @@
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    backend::{
+        conversion::{convert_credential, has_data_model, missing_fields, read_json, validate_credential, write_json},
+        mapping_file::load_mapping,
+        transformations::Transformation,
+    },
+    trace_dbg,
+};
+
+#[derive(Args, Debug)]
+pub struct BatchArgs {
+    /// Directory or glob pattern of the source credentials, e.g. `diplomas/` or `diplomas/**/*.json`.
+    #[arg(short, long)]
+    pub input: String,
+    /// Path to the mapping file that is applied to every source credential, or to a spine registry to compose it from.
+    #[arg(short, long)]
+    pub mapping: PathBuf,
+    /// Format of the source credentials, e.g. `ELM` or `OBv3`.
+    #[arg(long)]
+    pub from: String,
+    /// Format of the output credentials, e.g. `ELM` or `OBv3`.
+    #[arg(long)]
+    pub to: String,
+    /// Directory the converted credentials are written to, one file per source credential.
+    #[arg(short, long)]
+    pub output_dir: PathBuf,
+    /// Path the summary report is written to as JSON.
+    #[arg(short, long)]
+    pub report: Option<PathBuf>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Status {
+    Converted,
+    Invalid,
+    Failed,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FileReport {
+    pub input: PathBuf,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing_fields: Vec<String>,
+    /// Mappings that were skipped because their source has no value in the credential.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct BatchReport {
+    pub converted: usize,
+    pub invalid: usize,
+    pub failed: usize,
+    pub files: Vec<FileReport>,
+}
+
+impl From<Vec<FileReport>> for BatchReport {
+    fn from(files: Vec<FileReport>) -> Self {
+        let count = |status| files.iter().filter(|file| file.status == status).count();
+
+        BatchReport {
+            converted: count(Status::Converted),
+            invalid: count(Status::Invalid),
+            failed: count(Status::Failed),
+            files,
+        }
+    }
+}
+
+pub fn batch(args: BatchArgs) -> Result<()> {
+    let (base, inputs) = collect_inputs(&args.input)?;
+    if inputs.is_empty() {
+        bail!("no JSON files found for `{}`", args.input);
+    }
+
+    let transformations = Arc::new(load_mapping(&args.mapping, &args.from, &args.to)?);
+    if !has_data_model(&args.to) {
+        eprintln!(
+            "warning: `{}` has no data model, the outputs are not validated",
+            args.to
+        );
+    }
+
+    std::fs::create_dir_all(&args.output_dir)
+        .with_context(|| format!("failed to create `{}`", args.output_dir.display()))?;
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    let report = BatchReport::from(runtime.block_on(convert_all(inputs, base, transformations, &args)));
+
+    for file in &report.files {
+        for warning in &file.warnings {
+            eprintln!("warning: {}: {warning}", file.input.display());
+        }
+        match file.status {
+            Status::Converted => {}
+            Status::Invalid => {
+                eprintln!("invalid: {}", file.input.display());
+                for pointer in &file.missing_fields {
+                    eprintln!("    missing field: {pointer}");
+                }
+            }
+            Status::Failed => {
+                eprintln!(
+                    "failed: {}: {}",
+                    file.input.display(),
+                    file.error.as_deref().unwrap_or_default()
+                );
+            }
+        }
+    }
+    println!(
+        "{} converted, {} invalid, {} failed",
+        report.converted, report.invalid, report.failed
+    );
+
+    if let Some(path) = &args.report {
+        write_json(path, &report)?;
+    }
+
+    if report.invalid + report.failed > 0 {
+        bail!(
+            "{} of {} credentials could not be converted",
+            report.invalid + report.failed,
+            report.files.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Converts all source credentials in parallel. The reports are returned in the same order as the inputs.
+async fn convert_all(
+    inputs: Vec<PathBuf>,
+    base: PathBuf,
+    transformations: Arc<Vec<Transformation>>,
+    args: &BatchArgs,
+) -> Vec<FileReport> {
+    let handles: Vec<_> = inputs
+        .into_iter()
+        .map(|input| {
+            let transformations = Arc::clone(&transformations);
+            let (from, to) = (args.from.clone(), args.to.clone());
+            let output_path = output_path(&input, &base, &args.output_dir);
+            let handle = tokio::task::spawn_blocking({
+                let input = input.clone();
+                move || convert_file(input, &transformations, &from, &to, output_path)
+            });
+            (input, handle)
+        })
+        .collect();
+
+    let mut files = Vec::with_capacity(handles.len());
+    for (input, handle) in handles {
+        files.push(handle.await.unwrap_or_else(|e| FileReport {
+            input,
+            status: Status::Failed,
+            output: None,
+            missing_fields: vec![],
+            warnings: vec![],
+            error: Some(e.to_string()),
+        }));
+    }
+    files
+}
+
+fn convert_file(
+    input: PathBuf,
+    transformations: &[Transformation],
+    from: &str,
+    to: &str,
+    output_path: PathBuf,
+) -> FileReport {
+    let mut report = FileReport {
+        input,
+        status: Status::Failed,
+        output: None,
+        missing_fields: vec![],
+        warnings: vec![],
+        error: None,
+    };
+
+    let output = read_json::<Value>(&report.input)
+        .and_then(|credential| convert_credential(credential, transformations.to_vec(), from, to));
+
+    match output {
+        Ok((output, warnings)) => {
+            report.warnings = warnings.iter().map(ToString::to_string).collect();
+
+            if let Err(e) = validate_credential(to, &output) {
+                report.status = Status::Invalid;
+                report.missing_fields = missing_fields(to, &output).unwrap_or_default();
+                report.error = Some(format!("{e:#}"));
+                return report;
+            }
+
+            let written = match output_path.parent() {
+                Some(parent) => std::fs::create_dir_all(parent)
+                    .with_context(|| format!("failed to create `{}`", parent.display()))
+                    .and_then(|()| write_json(&output_path, &output)),
+                None => write_json(&output_path, &output),
+            };
+            match written {
+                Ok(()) => {
+                    report.status = Status::Converted;
+                    report.output = Some(output_path);
+                }
+                Err(e) => report.error = Some(format!("{e:#}")),
+            }
+        }
+        Err(e) => report.error = Some(format!("{e:#}")),
+    }
+
+    trace_dbg!(&report);
+    report
+}
+
+/// Returns all JSON files in the given directory, or all files matching the given glob pattern, together with the
+/// directory their paths are relative to in the output directory.
+fn collect_inputs(input: &str) -> Result<(PathBuf, Vec<PathBuf>)> {
+    let path = Path::new(input);
+    let base = if path.is_dir() {
+        path.to_path_buf()
+    } else {
+        glob_base(input)
+    };
+
+    let mut inputs = if path.is_dir() {
+        std::fs::read_dir(path)
+            .with_context(|| format!("failed to read `{input}`"))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension == "json"))
+            .collect::<Vec<_>>()
+    } else {
+        glob::glob(input)
+            .with_context(|| format!("invalid glob pattern `{input}`"))?
+            .filter_map(|entry| entry.ok())
+            .filter(|path| path.is_file())
+            .collect()
+    };
+
+    inputs.sort();
+    Ok((base, inputs))
+}
+
+/// Returns the directory of a glob pattern up to the first component with a wildcard.
+fn glob_base(pattern: &str) -> PathBuf {
+    let is_pattern = |component: &str| component.contains(['*', '?', '[']);
+
+    let mut base = PathBuf::new();
+    let mut components = Path::new(pattern).components().peekable();
+    while let Some(component) = components.next() {
+        // The last component is the file name, also when it has no wildcard
+        if components.peek().is_none() || is_pattern(&component.as_os_str().to_string_lossy()) {
+            break;
+        }
+        base.push(component);
+    }
+    base
+}
+
+/// Returns where the converted credential is written: at the path of the input relative to the base, so inputs with the
+/// same file name in different directories don't overwrite each other.
+fn output_path(input: &Path, base: &Path, output_dir: &Path) -> PathBuf {
+    let relative = input
+        .strip_prefix(base)
+        .ok()
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from(input.file_name().unwrap_or_default()));
+
+    output_dir.join(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_base_stops_at_the_first_wildcard() {
+        assert_eq!(glob_base("diplomas/**/*.json"), PathBuf::from("diplomas"));
+        assert_eq!(glob_base("a/b/x?.json"), PathBuf::from("a/b"));
+        assert_eq!(glob_base("a/b/x.json"), PathBuf::from("a/b"));
+        assert_eq!(glob_base("*.json"), PathBuf::new());
+    }
+
+    #[test]
+    fn output_paths_keep_subdirectories() {
+        let base = glob_base("diplomas/**/*.json");
+        let output_dir = Path::new("converted");
+
+        let a = output_path(Path::new("diplomas/a/x.json"), &base, output_dir);
+        let b = output_path(Path::new("diplomas/b/x.json"), &base, output_dir);
+        assert_eq!(a, PathBuf::from("converted/a/x.json"));
+        assert_eq!(b, PathBuf::from("converted/b/x.json"));
+        assert_eq!(
+            output_path(Path::new("x.json"), &PathBuf::new(), output_dir),
+            PathBuf::from("converted/x.json")
+        );
+    }
+
+    #[test]
+    fn directories_are_their_own_base() {
+        let directory = std::env::temp_dir().join(format!("impierce-mapper-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("x.json"), "{}").unwrap();
+
+        let collected = collect_inputs(directory.to_str().unwrap());
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let (base, inputs) = collected.unwrap();
+        assert_eq!(base, directory);
+        assert_eq!(
+            output_path(&inputs[0], &base, Path::new("out")),
+            PathBuf::from("out/x.json")
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use crate::backend::{
+    conversion::{read_json, write_json},
+    mapping_file::MappingFile,
+    spine::{flatten, Spine},
+};
+
+#[derive(Args, Debug)]
+pub struct ComposeArgs {
+    /// Path to the spine registry, which lists the mapping files of every format into and out of the spine.
+    #[arg(short, long)]
+    pub spine: PathBuf,
+    /// Format of the source credentials, e.g. `ELM` or `OBv3`.
+    #[arg(long)]
+    pub from: String,
+    /// Format of the output credentials, e.g. `ELM` or `OBv3`.
+    #[arg(long)]
+    pub to: String,
+    /// Path the composed mapping file is written to.
+    #[arg(short, long)]
+    pub output: PathBuf,
+    /// Rewrites the composed mapping into a direct mapping from the source to the output format, where possible.
+    #[arg(long)]
+    pub flatten: bool,
+}
+
+pub fn compose(args: ComposeArgs) -> Result<()> {
+    let spine: Spine = read_json(&args.spine)?;
+    let mut transformations = spine.compose(&args.spine, &args.from, &args.to)?;
+
+    if args.flatten {
+        let unflattened;
+        (transformations, unflattened) = flatten(transformations);
+
+        for path in unflattened {
+            eprintln!("not flattened: {path}");
+        }
+    }
+
+    write_json(&args.output, &MappingFile::new(&args.from, &args.to, transformations))?;
+    println!("{} -> {}", args.spine.display(), args.output.display());
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use anyhow::Result;
+use clap::Args;
+use serde_json::Value;
+use std::path::PathBuf;
+
+use crate::backend::{
+    conversion::{convert_credential, has_data_model, missing_fields, read_json, validate_credential, write_json},
+    mapping_file::load_mapping,
+};
+
+#[derive(Args, Debug)]
+pub struct ConvertArgs {
+    /// Path to the source credential.
+    #[arg(short, long)]
+    pub input: PathBuf,
+    /// Path to the mapping file that is applied to the source credential, or to a spine registry to compose it from.
+    #[arg(short, long)]
+    pub mapping: PathBuf,
+    /// Format of the source credential, e.g. `ELM` or `OBv3`.
+    #[arg(long)]
+    pub from: String,
+    /// Format of the output credential, e.g. `ELM` or `OBv3`.
+    #[arg(long)]
+    pub to: String,
+    /// Path the converted credential is written to.
+    #[arg(short, long)]
+    pub output: PathBuf,
+}
+
+pub fn convert(args: ConvertArgs) -> Result<()> {
+    let input: Value = read_json(&args.input)?;
+    let transformations = load_mapping(&args.mapping, &args.from, &args.to)?;
+
+    let (output, warnings) = convert_credential(input, transformations, &args.from, &args.to)?;
+    for warning in warnings {
+        eprintln!("warning: {warning}");
+    }
+
+    if !has_data_model(&args.to) {
+        eprintln!("warning: `{}` has no data model, the output is not validated", args.to);
+    }
+    if let Err(e) = validate_credential(&args.to, &output) {
+        for pointer in missing_fields(&args.to, &output)? {
+            eprintln!("missing field: {pointer}");
+        }
+        return Err(e);
+    }
+
+    write_json(&args.output, &output)?;
+    println!("{} -> {}", args.input.display(), args.output.display());
+
+    Ok(())
+}
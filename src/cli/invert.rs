@@ -0,0 +1,53 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+
+use crate::backend::{conversion::write_json, inversion, mapping_file::MappingFile};
+
+#[derive(Args, Debug)]
+pub struct InvertArgs {
+    /// Path to the mapping file that is inverted.
+    #[arg(short, long)]
+    pub mapping: PathBuf,
+    /// Path the inverse mapping file is written to.
+    #[arg(short, long)]
+    pub output: PathBuf,
+    /// Path the report of lossy and not inverted mappings is written to as JSON.
+    #[arg(short, long)]
+    pub report: Option<PathBuf>,
+}
+
+pub fn invert(args: InvertArgs) -> Result<()> {
+    let mut mapping_file = MappingFile::read(&args.mapping)?;
+    // The tables are checked for keys that share a value, which the inverse can't restore
+    mapping_file.load_tables(&args.mapping)?;
+
+    let (inverted, report) = inversion::invert(&mapping_file.mappings);
+
+    for mapping in &report.lossy {
+        eprintln!("lossy: {mapping}");
+    }
+    for mapping in &report.not_inverted {
+        eprintln!("not inverted: {mapping}");
+    }
+    println!(
+        "{} inverted, {} lossy, {} not inverted",
+        report.inverted,
+        report.lossy.len(),
+        report.not_inverted.len()
+    );
+
+    // The inverse maps in the opposite direction and is a new file
+    let metadata = &mut mapping_file.metadata;
+    std::mem::swap(&mut metadata.source, &mut metadata.target);
+    metadata.created = None;
+    mapping_file.mappings = inverted;
+    mapping_file.touch();
+
+    write_json(&args.output, &mapping_file)?;
+    if let Some(path) = &args.report {
+        write_json(path, &report)?;
+    }
+
+    Ok(())
+}
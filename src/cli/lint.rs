@@ -0,0 +1,50 @@
+use anyhow::{bail, Result};
+use clap::Args;
+use serde_json::Value;
+use std::path::PathBuf;
+
+use crate::backend::{
+    conversion::{read_json, write_json},
+    linter,
+    mapping_file::load_mapping,
+};
+
+#[derive(Args, Debug)]
+pub struct LintArgs {
+    /// Path to the mapping file that is checked, or to a spine registry to compose it from.
+    #[arg(short, long)]
+    pub mapping: PathBuf,
+    /// Format of the source credentials, e.g. `ELM` or `OBv3`.
+    #[arg(long)]
+    pub from: String,
+    /// Format of the output credentials, e.g. `ELM` or `OBv3`.
+    #[arg(long)]
+    pub to: String,
+    /// Path to a sample source credential, in which every source of the mapping should have a value.
+    #[arg(short, long)]
+    pub sample: Option<PathBuf>,
+    /// Path the findings are written to as JSON.
+    #[arg(short, long)]
+    pub report: Option<PathBuf>,
+}
+
+pub fn lint(args: LintArgs) -> Result<()> {
+    let transformations = load_mapping(&args.mapping, &args.from, &args.to)?;
+    let sample: Option<Value> = args.sample.as_ref().map(read_json).transpose()?;
+
+    let report = linter::lint(&transformations, &args.from, &args.to, sample);
+    if let Some(path) = &args.report {
+        write_json(path, &report)?;
+    }
+
+    let findings = report.findings();
+    for finding in &findings {
+        eprintln!("{finding}");
+    }
+    if !findings.is_empty() {
+        bail!("{} problems found in `{}`", findings.len(), args.mapping.display());
+    }
+    println!("no problems found in `{}`", args.mapping.display());
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+pub mod batch;
+pub mod compose;
+pub mod convert;
+pub mod invert;
+pub mod lint;
+
+use batch::{batch, BatchArgs};
+use clap::{Parser, Subcommand};
+use compose::{compose, ComposeArgs};
+use convert::{convert, ConvertArgs};
+use invert::{invert, InvertArgs};
+use lint::{lint, LintArgs};
+use std::path::PathBuf;
+
+use crate::trace_dbg;
+
+/// Converts credentials between the OpenBadges v3 and the ELM format. Starts the terminal interface when no command is
+/// given.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Resume the mapping session saved in the file, instead of starting on the first page.
+    #[arg(long, value_name = "SESSION")]
+    pub resume: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Convert a single credential with a mapping file, without starting the terminal interface.
+    Convert(ConvertArgs),
+    /// Convert a directory or glob of credentials with the same mapping file and report the results.
+    Batch(BatchArgs),
+    /// Compose the mapping between two formats from their mappings into and out of the spine, and write it to a file.
+    Compose(ComposeArgs),
+    /// Derive the mapping in the opposite direction from a mapping file and report the mappings that can't be inverted.
+    Invert(InvertArgs),
+    /// Check a mapping file for duplicate, conflicting and unused mappings, and sources missing in a sample credential.
+    Lint(LintArgs),
+}
+
+impl Command {
+    /// Runs the command and returns the exit code of the process.
+    pub fn run(self) -> i32 {
+        let result = match self {
+            Command::Convert(args) => convert(args),
+            Command::Batch(args) => batch(args),
+            Command::Compose(args) => compose(args),
+            Command::Invert(args) => invert(args),
+            Command::Lint(args) => lint(args),
+        };
+
+        match result {
+            Ok(()) => 0,
+            Err(e) => {
+                trace_dbg!(&e);
+                eprintln!("error: {e:#}");
+                1
+            }
+        }
+    }
+}
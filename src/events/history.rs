@@ -0,0 +1,269 @@
+use super::p2_p3_common::replay_mappings;
+use crate::{
+    backend::transformations::Transformation,
+    state::{AppState, Combiners, MappingOptions, Pages, Transformations},
+};
+
+/// Amount of actions that can be undone, the oldest are forgotten first.
+const MAX_HISTORY: usize = 100;
+
+/// The mapping actions on P2 and P3 that can be undone.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Confirm,
+    Clear,
+    SelectTransformation,
+    SelectField,
+    Edit,
+}
+
+/// Actions that can be undone and undone actions that can be redone, as the state before each of them.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    last_action: Option<Action>,
+}
+
+impl History {
+    /// Forgets all actions, for when the mappings are changed outside of P2 and P3.
+    pub fn clear(&mut self) {
+        *self = History::default();
+    }
+
+    /// Ends the edit that is typed, so the next edit is undone on its own.
+    pub fn end_edit(&mut self) {
+        if self.last_action == Some(Action::Edit) {
+            self.last_action = None;
+        }
+    }
+}
+
+/// Everything a mapping action can change: the confirmed mappings, the completed fields and the mapping options that are
+/// being filled in, on the page of the action. The output credential is not kept, it is rebuilt from the mappings.
+#[derive(Debug)]
+struct Snapshot {
+    page: Pages,
+    mappings: Vec<Transformation>,
+    missing_data_fields: Vec<(String, String)>,
+    completed_missing_fields: Vec<(usize, usize)>,
+    optional_fields: Vec<(String, String)>,
+    completed_optional_fields: Vec<(usize, usize)>,
+
+    selected_input_field: usize,
+    selected_missing_field: usize,
+    selected_optional_field: usize,
+    selected_input_fields: Vec<usize>,
+    selected_missing_fields: Vec<usize>,
+    selected_optional_fields: Vec<usize>,
+
+    select_mapping_option: bool,
+    selected_transformations_tab: bool,
+    mapping_option: MappingOptions,
+    transformations: Transformations,
+    selected_transformation: usize,
+    selected_transformations: Vec<Transformations>,
+    parameters_input: Option<Transformations>,
+    second_parameter: bool,
+    slice_input: (String, String),
+    regex_input: (String, String),
+    timezone_input: String,
+    lookup_input: (String, String),
+    condition_input: (String, String),
+    dividers: String,
+    combiner: Combiners,
+    separator: String,
+    constant_input: String,
+}
+
+impl Snapshot {
+    fn take(state: &AppState) -> Self {
+        Snapshot {
+            page: state.page,
+            mappings: state.mappings.clone(),
+            missing_data_fields: state.missing_data_fields.clone(),
+            completed_missing_fields: state.completed_missing_fields.clone(),
+            optional_fields: state.optional_fields.clone(),
+            completed_optional_fields: state.completed_optional_fields.clone(),
+
+            selected_input_field: state.selected_input_field,
+            selected_missing_field: state.selected_missing_field,
+            selected_optional_field: state.selected_optional_field,
+            selected_input_fields: state.selected_input_fields.clone(),
+            selected_missing_fields: state.selected_missing_fields.clone(),
+            selected_optional_fields: state.selected_optional_fields.clone(),
+
+            select_mapping_option: state.select_mapping_option,
+            selected_transformations_tab: state.selected_transformations_tab,
+            mapping_option: state.mapping_option,
+            transformations: state.transformations,
+            selected_transformation: state.selected_transformation,
+            selected_transformations: state.selected_transformations.clone(),
+            parameters_input: state.parameters_input,
+            second_parameter: state.second_parameter,
+            slice_input: state.slice_input.clone(),
+            regex_input: state.regex_input.clone(),
+            timezone_input: state.timezone_input.clone(),
+            lookup_input: state.lookup_input.clone(),
+            condition_input: state.condition_input.clone(),
+            dividers: state.dividers.clone(),
+            combiner: state.combiner,
+            separator: state.separator.clone(),
+            constant_input: state.constant_input.clone(),
+        }
+    }
+
+    /// Restores the state and goes back to the page of the action.
+    fn restore(self, state: &mut AppState) {
+        state.page = self.page;
+        state.mappings = self.mappings;
+        state.missing_data_fields = self.missing_data_fields;
+        state.completed_missing_fields = self.completed_missing_fields;
+        state.optional_fields = self.optional_fields;
+        state.completed_optional_fields = self.completed_optional_fields;
+
+        state.selected_input_field = self.selected_input_field;
+        state.selected_missing_field = self.selected_missing_field;
+        state.selected_optional_field = self.selected_optional_field;
+        state.selected_input_fields = self.selected_input_fields;
+        state.selected_missing_fields = self.selected_missing_fields;
+        state.selected_optional_fields = self.selected_optional_fields;
+
+        state.select_mapping_option = self.select_mapping_option;
+        state.selected_transformations_tab = self.selected_transformations_tab;
+        state.mapping_option = self.mapping_option;
+        state.transformations = self.transformations;
+        state.selected_transformation = self.selected_transformation;
+        state.selected_transformations = self.selected_transformations;
+        state.parameters_input = self.parameters_input;
+        state.second_parameter = self.second_parameter;
+        state.slice_input = self.slice_input;
+        state.regex_input = self.regex_input;
+        state.timezone_input = self.timezone_input;
+        state.lookup_input = self.lookup_input;
+        state.condition_input = self.condition_input;
+        state.dividers = self.dividers;
+        state.combiner = self.combiner;
+        state.separator = self.separator;
+        state.constant_input = self.constant_input;
+
+        if let Err(e) = replay_mappings(state) {
+            state.mapping_error = Some(e);
+        }
+    }
+}
+
+/// Remembers the state before the action, so it can be undone. Consecutive edits are undone at once, like a typed word.
+pub fn record(state: &mut AppState, action: Action) {
+    if action == Action::Edit && state.history.last_action == Some(Action::Edit) {
+        return;
+    }
+
+    push(state, action, Snapshot::take(state));
+}
+
+/// Remembers the state before an action that can fail or do nothing, only when the action returns that it was applied
+/// and no error is shown.
+pub fn record_if_applied(state: &mut AppState, action: Action, perform: impl FnOnce(&mut AppState) -> bool) {
+    let snapshot = Snapshot::take(state);
+    if perform(state) && state.mapping_error.is_none() {
+        push(state, action, snapshot);
+    }
+}
+
+fn push(state: &mut AppState, action: Action, snapshot: Snapshot) {
+    let history = &mut state.history;
+    history.undo.push(snapshot);
+    if history.undo.len() > MAX_HISTORY {
+        history.undo.remove(0);
+    }
+    history.redo.clear();
+    history.last_action = Some(action);
+}
+
+/// Restores the state before the last action.
+pub fn undo(state: &mut AppState) {
+    if let Some(snapshot) = state.history.undo.pop() {
+        let current = Snapshot::take(state);
+        state.history.redo.push(current);
+        state.history.last_action = None;
+        snapshot.restore(state);
+    }
+}
+
+/// Does the last undone action again.
+pub fn redo(state: &mut AppState) {
+    if let Some(snapshot) = state.history.redo.pop() {
+        let current = Snapshot::take(state);
+        state.history.undo.push(current);
+        state.history.last_action = None;
+        snapshot.restore(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::error::MappingError, events::p2_p3_common::confirm_mapping};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn field(pointer: &str, value: &str) -> (String, String) {
+        (pointer.to_string(), value.to_string())
+    }
+
+    fn state() -> AppState {
+        let loaded = HashMap::from_iter([
+            ("ELM".to_string(), json!({ "title": "Rust" })),
+            ("OBv3".to_string(), json!({})),
+        ]);
+        AppState {
+            page: Pages::ManualMappingP2,
+            input_fields: vec![field("", ""), field("/title", "\"Rust\"")],
+            selected_input_field: 1,
+            missing_data_fields: vec![field("", ""), field("/name", "")],
+            selected_missing_field: 1,
+            repository: loaded.clone().into(),
+            loaded_repository: loaded.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn undo_goes_back_to_the_page_and_rebuilds_the_output() {
+        let mut state = state();
+        confirm_mapping(&mut state);
+        assert_eq!(state.repository["OBv3"], json!({ "name": "Rust" }));
+        state.page = Pages::UnusedDataP3;
+
+        undo(&mut state);
+        assert_eq!(state.page, Pages::ManualMappingP2);
+        assert!(state.mappings.is_empty());
+        assert_eq!(state.repository["OBv3"], json!({}));
+        assert!(state.completed_missing_fields.is_empty());
+
+        redo(&mut state);
+        assert_eq!(state.page, Pages::UnusedDataP3);
+        assert_eq!(state.repository["OBv3"], json!({ "name": "Rust" }));
+        assert_eq!(state.completed_missing_fields, [(1, 1)]);
+    }
+
+    #[test]
+    fn failed_actions_are_not_recorded() {
+        let mut state = state();
+        record_if_applied(&mut state, Action::Confirm, |state| {
+            state.mapping_error = Some(MappingError::PathNotFound("/title".to_string()));
+            true
+        });
+        assert!(state.history.undo.is_empty());
+
+        // Confirming a constant without an output field does nothing
+        state.mapping_error = None;
+        state.selected_missing_field = 0;
+        state.mapping_option = MappingOptions::Constant;
+        state.select_mapping_option = false;
+        state.constant_input = "Rust".to_string();
+        confirm_mapping(&mut state);
+        assert!(state.history.undo.is_empty());
+    }
+}
@@ -1,23 +1,35 @@
 use crate::state::{AppState, Pages};
-use crossterm::event::{self};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, MouseEventKind};
 
+pub mod history;
 pub mod p1_handler;
 pub mod p2_handler;
 pub mod p2_p3_common;
 pub mod p3_handler;
 pub mod p4_handler;
+pub mod rules_handler;
 
 pub use p1_handler::p1_handler;
 pub use p2_handler::p2_handler;
 pub use p3_handler::p3_handler;
 pub use p4_handler::p4_handler;
 use ratatui::layout::Rect;
+pub use rules_handler::rules_handler;
 
 pub fn events_handler(state: &mut AppState) -> Result<bool, std::io::Error> {
     let mut quit = false;
 
     if event::poll(std::time::Duration::from_millis(16))? {
         let event = event::read()?;
+
+        // An error popup blocks all other input until it is closed.
+        if state.mapping_error.is_some() {
+            if is_close_event(&event) {
+                state.mapping_error = None;
+            }
+            return Ok(false);
+        }
+
         match state.page {
             Pages::InputPromptsP1 => {
                 quit = p1_handler(event, state)?;
@@ -28,7 +40,10 @@ pub fn events_handler(state: &mut AppState) -> Result<bool, std::io::Error> {
             Pages::UnusedDataP3 => {
                 quit = p3_handler(event, state)?;
             }
-            Pages::EndP4 => {
+            Pages::MappingRulesP4 => {
+                quit = rules_handler(event, state)?;
+            }
+            Pages::EndP5 => {
                 quit = p4_handler(event, state)?;
             }
         }
@@ -39,6 +54,14 @@ pub fn events_handler(state: &mut AppState) -> Result<bool, std::io::Error> {
 
 //////////     HELPERS     //////////
 
+fn is_close_event(event: &Event) -> bool {
+    match event {
+        Event::Key(key) => key.kind == KeyEventKind::Press && matches!(key.code, KeyCode::Esc | KeyCode::Enter),
+        Event::Mouse(mouse_event) => matches!(mouse_event.kind, MouseEventKind::Up(_)),
+        _ => false,
+    }
+}
+
 fn is_mouse_over_area(area: Rect, mouse_x: u16, mouse_y: u16) -> bool {
     mouse_x >= area.x && mouse_x < area.x + area.width && mouse_y >= area.y && mouse_y < area.y + area.height
 }
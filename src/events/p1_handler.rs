@@ -1,13 +1,39 @@
-use crate::backend::preload_p2::preload_p2;
-use crate::state::{AppState, P1Prompts};
+use crate::backend::{
+    error::MappingError,
+    linter::preflight,
+    preload_p2::preload_p2,
+    session::{autosave_path, discard_autosave, Session},
+};
+use crate::state::{AppState, P1Prompts, Pages};
 use crossterm::event::{self, Event, KeyCode::*, KeyEventKind};
 use std::path::Path;
 
-use super::is_mouse_over_area;
+use super::{is_mouse_over_area, p2_p3_common::replay_mappings};
 
 pub fn p1_handler(event: Event, state: &mut AppState) -> Result<bool, std::io::Error> {
     if let event::Event::Key(key) = event {
-        if key.kind == KeyEventKind::Press {
+        // The autosaved session is recovered or discarded before anything else
+        if key.kind == KeyEventKind::Press && state.recovery_warning.is_some() {
+            match key.code {
+                Enter => recover_session(state),
+                Esc => {
+                    state.recovery_warning = None;
+                    discard_autosave();
+                }
+                _ => {}
+            }
+        }
+        // The findings of the linter are confirmed or dismissed before anything else
+        else if key.kind == KeyEventKind::Press && !state.lint_warning.is_empty() {
+            match key.code {
+                Enter => {
+                    state.lint_warning.clear();
+                    continue_p2(state);
+                }
+                Esc => state.lint_warning.clear(),
+                _ => {}
+            }
+        } else if key.kind == KeyEventKind::Press {
             match key.code {
                 Esc => {
                     if state.overwrite_warning {
@@ -63,11 +89,19 @@ pub fn p1_handler(event: Event, state: &mut AppState) -> Result<bool, std::io::E
                         state.output_path.pop();
                     }
                     P1Prompts::MappingFile => {
-                        state.mapping_path.pop();
+                        // An empty layer is removed, so the layer below it is typed again
+                        if state.mapping_paths.len() > 1 && state.mapping_paths.last().is_some_and(String::is_empty) {
+                            state.mapping_paths.pop();
+                        } else if let Some(path) = state.mapping_paths.last_mut() {
+                            path.pop();
+                        }
                     }
                     P1Prompts::CustomMapping => {
                         state.custom_mapping_path.pop();
                     }
+                    P1Prompts::Session => {
+                        state.session_path.pop();
+                    }
                     _ => {}
                 },
                 Char(value) => {
@@ -79,12 +113,18 @@ pub fn p1_handler(event: Event, state: &mut AppState) -> Result<bool, std::io::E
                             P1Prompts::Output => {
                                 state.output_path.push(value);
                             }
-                            P1Prompts::MappingFile => {
-                                state.mapping_path.push(value);
-                            }
+                            // A comma starts the next layer
+                            P1Prompts::MappingFile => match state.mapping_paths.last_mut() {
+                                Some(_) if value == ',' => state.mapping_paths.push(String::new()),
+                                Some(path) => path.push(value),
+                                None => state.mapping_paths.push(value.to_string()),
+                            },
                             P1Prompts::CustomMapping => {
                                 state.custom_mapping_path.push(value);
                             }
+                            P1Prompts::Session => {
+                                state.session_path.push(value);
+                            }
                             _ => {}
                         }
                     }
@@ -99,19 +139,24 @@ pub fn p1_handler(event: Event, state: &mut AppState) -> Result<bool, std::io::E
             if is_mouse_over_area(state.complete_button, mouse_event.column, mouse_event.row) {
                 // init paths for if statements
                 let input_path = Path::new(&state.input_path);
-                let mapping_path = Path::new(&state.mapping_path);
                 let output_path = Path::new(&state.output_path);
-                let custom_mapping_path = Path::new(&state.custom_mapping_path);
 
+                // Recover the autosaved session.
+                if state.recovery_warning.is_some() {
+                    recover_session(state);
+                }
+                // Continue despite the findings of the linter.
+                else if !state.lint_warning.is_empty() {
+                    state.lint_warning.clear();
+                    continue_p2(state);
+                }
                 // Check if user is at the end of the prompts and if one of the prompts will overwrite a file and show overwrite warning.
-                if (output_path.is_file() || custom_mapping_path.is_file()) && !state.overwrite_warning {
+                else if output_path.is_file() && !state.overwrite_warning {
                     state.overwrite_warning = true;
                 }
                 // Check if all prompts are valid and go to next page.
-                else if input_path.is_file() && mapping_path.is_file() && !state.output_path.is_empty() {
-                    state.page.next();
-                    preload_p2(state);
-                    state.overwrite_warning = false;
+                else if input_path.is_file() && state.mapping_files_exist() && !state.output_path.is_empty() {
+                    load_p2(state);
                 }
             }
         }
@@ -129,25 +174,24 @@ fn handle_enter_p1(state: &mut AppState) -> bool {
     // init paths for if statements
     let input_path = Path::new(&state.input_path);
     let output_path = Path::new(&state.output_path);
-    let mapping_path = Path::new(&state.mapping_path);
-    let custom_mapping_path = Path::new(&state.custom_mapping_path);
 
+    // Resume the saved session instead of starting a new one.
+    if state.p1_prompts == P1Prompts::Session && Path::new(&state.session_path).is_file() {
+        if let Err(e) = resume_session(state, state.session_path.clone()) {
+            state.mapping_error = Some(e);
+        }
+    }
     // Check if user is at the end of the prompts and if one of the prompts will overwrite a file and show overwrite warning.
-    if state.p1_prompts == P1Prompts::CustomMapping
-        && (output_path.is_file() || custom_mapping_path.is_file())
-        && !state.overwrite_warning
-    {
+    else if state.p1_prompts == P1Prompts::Session && output_path.is_file() && !state.overwrite_warning {
         state.overwrite_warning = true;
     }
     // Check if user is at the end (overwrite warning will only pop up at the end) and if the other prompts are valid and go to next page.
-    else if (state.p1_prompts == P1Prompts::CustomMapping || state.overwrite_warning)
+    else if (state.p1_prompts == P1Prompts::Session || state.overwrite_warning)
         && input_path.is_file()
-        && mapping_path.is_file()
+        && state.mapping_files_exist()
         && !state.output_path.is_empty()
     {
-        state.page.next();
-        preload_p2(state);
-        state.overwrite_warning = false;
+        load_p2(state);
     }
     // Close overwrite warning if user is not at the end of the prompts or some prompts are valid and stay on page.
     else if state.overwrite_warning {
@@ -158,3 +202,152 @@ fn handle_enter_p1(state: &mut AppState) -> bool {
 
     false
 }
+
+fn load_p2(state: &mut AppState) {
+    // Show the findings of the linter first, or what went wrong if the files can't be linted
+    match preflight(state) {
+        Ok(findings) => state.lint_warning = findings,
+        Err(e) => state.mapping_error = Some(e),
+    }
+    if !state.lint_warning.is_empty() || state.mapping_error.is_some() {
+        state.overwrite_warning = false;
+        return;
+    }
+
+    continue_p2(state);
+}
+
+fn continue_p2(state: &mut AppState) {
+    // Stay on this page and show what went wrong if the files can't be loaded or mapped.
+    match preload_p2(state) {
+        Ok(()) => state.page.next(),
+        Err(e) => state.mapping_error = Some(e),
+    }
+    state.overwrite_warning = false;
+}
+
+fn recover_session(state: &mut AppState) {
+    state.recovery_warning = None;
+    if let Err(e) = resume_session(state, autosave_path()) {
+        state.mapping_error = Some(e);
+    }
+    // The recovered mappings are not in the session file
+    state.saved_mappings.clear();
+}
+
+/// Loads the files of a saved session, restores its mappings and completed fields and rebuilds the output from the
+/// mappings. The completed fields that no longer exist, because the files changed since the session was saved, are left
+/// out and shown.
+pub fn resume_session(state: &mut AppState, path: impl AsRef<Path>) -> Result<(), MappingError> {
+    let path = path.as_ref();
+    let session = Session::read(path)?;
+
+    state.input_path = session.input_path;
+    state.mapping_paths = session.mapping_paths;
+    state.output_path = session.output_path;
+    state.custom_mapping_path = session.custom_mapping_path;
+    state.mapping = session.mapping;
+    preload_p2(state)?;
+
+    // The output is rebuilt from the mappings, so it follows the files when they changed since the session was saved
+    state.mappings = session.mappings;
+    state.saved_mappings = serde_json::to_string(&state.mappings).unwrap_or_default();
+    replay_mappings(state)?;
+
+    let input_fields = &state.input_fields;
+    let mut gone: Vec<String> = vec![];
+    for (fields, completed_fields, saved) in [
+        (
+            &mut state.missing_data_fields,
+            &mut state.completed_missing_fields,
+            session.completed_missing_fields,
+        ),
+        (
+            &mut state.optional_fields,
+            &mut state.completed_optional_fields,
+            session.completed_optional_fields,
+        ),
+    ] {
+        completed_fields.clear();
+        for (pointer, input_pointer) in saved {
+            let field = fields.iter().position(|(field, _)| *field == pointer);
+            let input_field = input_fields.iter().position(|(field, _)| *field == input_pointer);
+            match (field, input_field) {
+                (Some(field), Some(input_field)) => completed_fields.push((field, input_field)),
+                (None, _) => gone.push(pointer),
+                (_, None) => gone.push(input_pointer),
+            }
+        }
+    }
+
+    state.page = match session.page {
+        Pages::UnusedDataP3 | Pages::MappingRulesP4 => session.page,
+        _ => Pages::ManualMappingP2,
+    };
+    state.overwrite_warning = false;
+
+    if !gone.is_empty() {
+        gone.sort();
+        gone.dedup();
+        state.mapping_error = Some(MappingError::InvalidFile {
+            path: path.display().to_string(),
+            message: format!(
+                "the fields `{}` no longer exist, they are no longer completed",
+                gone.join("`, `")
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resuming_matches_the_completed_fields_again() {
+        let mut state = AppState {
+            input_path: "res/elm_example.json".to_string(),
+            mapping_paths: vec!["res/mapping_empty.json".to_string()],
+            output_path: std::env::temp_dir()
+                .join(format!("resumed_output_{}.json", std::process::id()))
+                .display()
+                .to_string(),
+            page: Pages::UnusedDataP3,
+            ..Default::default()
+        };
+        preload_p2(&mut state).unwrap();
+        state.completed_missing_fields = vec![(1, 1)];
+        let (pointer, input_pointer) = (state.missing_data_fields[1].0.clone(), state.input_fields[1].0.clone());
+
+        let mut session = Session::from(&state);
+        assert_eq!(session.completed_missing_fields, [(pointer.clone(), input_pointer)]);
+
+        // A field of the output and a field of the input that are no longer there
+        session
+            .completed_missing_fields
+            .insert(0, ("/gone".to_string(), String::new()));
+        session
+            .completed_missing_fields
+            .push((pointer.clone(), "/removed".to_string()));
+        let path = std::env::temp_dir().join(format!("resumed_session_{}.json", std::process::id()));
+        session.write(&path).unwrap();
+
+        let mut resumed = AppState::default();
+        let result = resume_session(&mut resumed, &path);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&state.output_path);
+        result.unwrap();
+        assert_eq!(resumed.page, Pages::UnusedDataP3);
+        assert_eq!(resumed.missing_data_fields[1].0, pointer);
+        assert_eq!(resumed.completed_missing_fields, [(1, 1)]);
+        assert_eq!(
+            resumed.mapping_error,
+            Some(MappingError::InvalidFile {
+                path: path.display().to_string(),
+                message: "the fields `/gone`, `/removed` no longer exist, they are no longer completed".to_string(),
+            })
+        );
+    }
+}
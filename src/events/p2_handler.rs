@@ -1,16 +1,23 @@
 use crate::{
+    history::{redo, undo},
     p2_p3_common::{
         handle_backspace, handle_char, handle_down, handle_enter, handle_esc, handle_f2, handle_left, handle_mouse_up,
-        handle_right, handle_scroll_down, handle_scroll_up, handle_tab, handle_up,
+        handle_right, handle_scroll_down, handle_scroll_up, handle_tab, handle_up, save_session,
     },
     state::AppState,
 };
 
-use crossterm::event::{self, Event, KeyCode::*, KeyEventKind};
+use crossterm::event::{self, Event, KeyCode::*, KeyEventKind, KeyModifiers};
 
 pub fn p2_handler(event: Event, state: &mut AppState) -> Result<bool, std::io::Error> {
     if let event::Event::Key(key) = event {
         if key.kind == KeyEventKind::Press {
+            let control = key.modifiers.contains(KeyModifiers::CONTROL);
+            // Typing continues the edit that is undone at once, any other key ends it
+            if !matches!(key.code, Char(_) | Backspace) {
+                state.history.end_edit();
+            }
+
             match key.code {
                 Esc => {
                     handle_esc(state);
@@ -41,6 +48,15 @@ pub fn p2_handler(event: Event, state: &mut AppState) -> Result<bool, std::io::E
                         return Ok(true);
                     }
                 }
+                Char('z') if control => {
+                    undo(state);
+                }
+                Char('y') | Char('Z') if control => {
+                    redo(state);
+                }
+                Char('s') if control => {
+                    save_session(state);
+                }
                 Char(char) => {
                     handle_char(state, char);
                 }
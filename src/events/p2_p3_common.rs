@@ -1,11 +1,28 @@
 use crossterm::event::MouseEvent;
+use serde_json::Value;
 use std::char;
 use std::io::Write;
+use std::path::Path;
 
-use super::is_mouse_over_area;
+use super::{
+    history::{record, record_if_applied, Action},
+    is_mouse_over_area,
+};
 use crate::{
-    backend::{repository::update_repository, selector::selector},
-    state::{AppState, MappingOptions, P2P3Tabs, Pages, Transformations},
+    backend::{
+        error::MappingError,
+        jsonpointer::{normalize, JsonPath, JsonPointer},
+        layers::{destinations, writes_any},
+        mapping_file::MappingFile,
+        repository::{update_repository, Repository},
+        selector::{
+            constant_transformation, many_to_one_transformation, one_to_many_transformation, pipeline_transformation,
+            selector,
+        },
+        session::Session,
+        transformations::Transformation,
+    },
+    state::{AppState, Combiners, MappingOptions, P2P3Tabs, Pages, Transformations},
     trace_dbg,
 };
 
@@ -20,6 +37,10 @@ pub fn handle_esc(state: &mut AppState) {
     else if state.popup_mapping_p2_p3 {
         clear_popup(state);
     }
+    // Close the parameters bar of a transformation
+    else if state.parameters_input.is_some() {
+        state.parameters_input = None;
+    }
     // clear mapping
     else if state.p2_p3_tabs == P2P3Tabs::MappingOptions && !state.select_mapping_option {
         clear_mapping_options(state);
@@ -31,8 +52,14 @@ pub fn handle_esc(state: &mut AppState) {
 }
 
 pub fn handle_backspace(state: &mut AppState) {
+    // Delete a character from the active parameter of a transformation
+    if state.parameters_input.is_some() && (state.p2_p3_tabs == P2P3Tabs::MappingOptions || state.popup_mapping_p2_p3) {
+        record(state, Action::Edit);
+        active_parameter(state).pop();
+    }
     // Delete a selected transformation from the list of selected transformations
-    if state.selected_transformations_tab && !state.selected_transformations.is_empty() {
+    else if state.selected_transformations_tab && !state.selected_transformations.is_empty() {
+        record(state, Action::SelectTransformation);
         state.selected_transformations.remove(state.selected_transformation);
         if state.selected_transformation > 0 {
             state.selected_transformation -= 1;
@@ -40,13 +67,31 @@ pub fn handle_backspace(state: &mut AppState) {
     }
     // Delete a character from the dividers
     else if state.p2_p3_tabs == P2P3Tabs::MappingOptions && state.mapping_option == MappingOptions::OneToMany {
+        record(state, Action::Edit);
         state.dividers.pop();
     }
+    // Delete a character from the separator
+    else if state.p2_p3_tabs == P2P3Tabs::MappingOptions && state.mapping_option == MappingOptions::ManyToOne {
+        record(state, Action::Edit);
+        state.separator.pop();
+    }
+    // Delete a character from the constant
+    else if state.mapping_option == MappingOptions::Constant
+        && !state.select_mapping_option
+        && (state.p2_p3_tabs == P2P3Tabs::MappingOptions || state.popup_mapping_p2_p3)
+    {
+        record(state, Action::Edit);
+        state.constant_input.pop();
+    }
 }
 
 pub fn handle_tab(state: &mut AppState) {
+    // Switch between the parameters of a transformation
+    if state.p2_p3_tabs == P2P3Tabs::MappingOptions && state.parameters_input.is_some() {
+        state.second_parameter = !state.second_parameter;
+    }
     // Check if inside Transformations bar in the transformations tab and switch to the selected transformations tab
-    if state.p2_p3_tabs == P2P3Tabs::MappingOptions
+    else if state.p2_p3_tabs == P2P3Tabs::MappingOptions
         && !state.select_mapping_option
         && state.mapping_option == MappingOptions::Transformations
         && !state.selected_transformations_tab
@@ -72,8 +117,12 @@ pub fn handle_tab(state: &mut AppState) {
 }
 
 pub fn handle_f2(state: &mut AppState) {
+    // Switch between the parameters of a transformation
+    if state.p2_p3_tabs == P2P3Tabs::MappingOptions && state.parameters_input.is_some() {
+        state.second_parameter = !state.second_parameter;
+    }
     // Check if inside Transformations bar on the selected_transformations tab and switch to the transformations tab
-    if state.p2_p3_tabs == P2P3Tabs::MappingOptions
+    else if state.p2_p3_tabs == P2P3Tabs::MappingOptions
         && !state.select_mapping_option
         && state.mapping_option == MappingOptions::Transformations
         && state.selected_transformations_tab
@@ -140,10 +189,18 @@ pub fn handle_down(state: &mut AppState) {
     }
 }
 pub fn handle_left(state: &mut AppState) {
+    // Switch between the parameters of a transformation
+    if state.p2_p3_tabs == P2P3Tabs::MappingOptions && state.parameters_input.is_some() {
+        state.second_parameter = !state.second_parameter;
+    }
     // Move through mapping options bar, loops.
-    if state.p2_p3_tabs == P2P3Tabs::MappingOptions && state.select_mapping_option {
+    else if state.p2_p3_tabs == P2P3Tabs::MappingOptions && state.select_mapping_option {
         state.mapping_option.prev();
     }
+    // Move through combiners bar, loops
+    else if state.p2_p3_tabs == P2P3Tabs::MappingOptions && state.mapping_option == MappingOptions::ManyToOne {
+        state.combiner.prev();
+    }
     // Move through transformation bar, loops
     else if state.p2_p3_tabs == P2P3Tabs::MappingOptions
         && !state.select_mapping_option
@@ -168,10 +225,18 @@ pub fn handle_left(state: &mut AppState) {
 }
 
 pub fn handle_right(state: &mut AppState) {
+    // Switch between the parameters of a transformation
+    if state.p2_p3_tabs == P2P3Tabs::MappingOptions && state.parameters_input.is_some() {
+        state.second_parameter = !state.second_parameter;
+    }
     // Move through mapping options bar, loops.
-    if state.p2_p3_tabs == P2P3Tabs::MappingOptions && state.select_mapping_option {
+    else if state.p2_p3_tabs == P2P3Tabs::MappingOptions && state.select_mapping_option {
         state.mapping_option.next();
     }
+    // Move through combiners bar, loops
+    else if state.p2_p3_tabs == P2P3Tabs::MappingOptions && state.mapping_option == MappingOptions::ManyToOne {
+        state.combiner.next();
+    }
     // Move through transformation bar, loops
     else if state.p2_p3_tabs == P2P3Tabs::MappingOptions
         && !state.select_mapping_option
@@ -197,9 +262,35 @@ pub fn handle_right(state: &mut AppState) {
 }
 
 pub fn handle_char(state: &mut AppState, char: char) {
-    if state.popup_mapping_p2_p3 && state.mapping_option == MappingOptions::OneToMany {
+    // Add a character to the active parameter of a transformation, also while its result is shown in the popup
+    if state.parameters_input.is_some() && (state.p2_p3_tabs == P2P3Tabs::MappingOptions || state.popup_mapping_p2_p3) {
+        record(state, Action::Edit);
+        active_parameter(state).push(char);
+    }
+    // Add a character to the dividers
+    else if state.mapping_option == MappingOptions::OneToMany
+        && !state.select_mapping_option
+        && (state.p2_p3_tabs == P2P3Tabs::MappingOptions || state.popup_mapping_p2_p3)
+    {
+        record(state, Action::Edit);
         state.dividers.push(char);
     }
+    // Add a character to the separator
+    else if state.mapping_option == MappingOptions::ManyToOne
+        && !state.select_mapping_option
+        && (state.p2_p3_tabs == P2P3Tabs::MappingOptions || state.popup_mapping_p2_p3)
+    {
+        record(state, Action::Edit);
+        state.separator.push(char);
+    }
+    // Add a character to the constant
+    else if state.mapping_option == MappingOptions::Constant
+        && !state.select_mapping_option
+        && (state.p2_p3_tabs == P2P3Tabs::MappingOptions || state.popup_mapping_p2_p3)
+    {
+        record(state, Action::Edit);
+        state.constant_input.push(char);
+    }
 }
 
 pub fn handle_enter(state: &mut AppState) -> bool {
@@ -215,23 +306,50 @@ pub fn handle_enter(state: &mut AppState) -> bool {
                 if state.select_mapping_option {
                     // Fast-track mapping, Copy to output result value and reset values
                     if state.mapping_option == MappingOptions::DirectCopy {
-                        selector(state);
-                        confirm_mapping(state);
+                        match selector(state) {
+                            Ok(()) => confirm_mapping(state),
+                            Err(e) => state.mapping_error = Some(e),
+                        }
                     }
                     // Switch from mapping options tab to respective tab
                     else {
                         state.select_mapping_option = false;
                     }
                 }
+                // Select a transformation with parameters if it hasn't already been selected and enter its parameters
+                else if state.mapping_option == MappingOptions::Transformations
+                    && !state.selected_transformations_tab
+                    && state.parameters_input.is_none()
+                    && matches!(
+                        state.transformations,
+                        Transformations::Slice
+                            | Transformations::Regex
+                            | Transformations::DateTime
+                            | Transformations::Lookup
+                            | Transformations::Condition
+                    )
+                {
+                    record(state, Action::SelectTransformation);
+                    if !state.selected_transformations.contains(&state.transformations) {
+                        state.selected_transformations.push(state.transformations);
+                    }
+                    state.parameters_input = Some(state.transformations);
+                    state.second_parameter = false;
+                }
                 // Select a transformation if it hasn't already been selected
                 else if state.mapping_option == MappingOptions::Transformations
                     && !state.selected_transformations_tab
                     && !state.selected_transformations.contains(&state.transformations)
                 {
+                    record(state, Action::SelectTransformation);
                     state.selected_transformations.push(state.transformations);
                 }
-                // If transformation(s) selected open the view popup to show the result.
-                else if state.selected_transformations_tab {
+                // If transformation(s), parameters or the combiner are selected open the view popup to show the result.
+                else if state.selected_transformations_tab
+                    || state.parameters_input.is_some()
+                    || state.mapping_option == MappingOptions::ManyToOne
+                    || state.mapping_option == MappingOptions::Constant
+                {
                     if !state.popup_mapping_p2_p3 {
                         state.popup_mapping_p2_p3 = true;
                     } else {
@@ -249,6 +367,22 @@ pub fn handle_enter(state: &mut AppState) -> bool {
                     confirm_mapping(state);
                 }
             }
+            // Select or deselect an input field as source of the ManyToOne mapping
+            P2P3Tabs::InputFields
+                if state.mapping_option == MappingOptions::ManyToOne
+                    && !state.select_mapping_option
+                    && !state.popup_mapping_p2_p3 =>
+            {
+                toggle_source(state);
+            }
+            // Select or deselect an output field as destination of the OneToMany mapping
+            P2P3Tabs::OutputFields
+                if state.mapping_option == MappingOptions::OneToMany
+                    && !state.select_mapping_option
+                    && !state.popup_mapping_p2_p3 =>
+            {
+                toggle_destination(state);
+            }
             _ => {
                 // Complete a mapping from the view popup
                 if state.popup_mapping_p2_p3 {
@@ -332,11 +466,9 @@ pub fn handle_scroll_up(state: &mut AppState, mouse_event: MouseEvent) {
 
 pub fn handle_mouse_up(state: &mut AppState, mouse_event: MouseEvent) {
     if is_mouse_over_area(state.complete_button, mouse_event.column, mouse_event.row) {
-        if state.missing_data_fields.len() - 1 == state.completed_missing_fields.len() {
-            next_page(state);
-        } else if state.page == Pages::UnusedDataP3 {
+        // The files are saved after the mapping rules are reviewed on the next page
+        if state.missing_fields_completed() || state.page == Pages::UnusedDataP3 {
             next_page(state);
-            create_output_files(state);
         } else {
             state.uncompleted_warning = true;
         }
@@ -382,26 +514,72 @@ pub fn next_page(state: &mut AppState) {
     state.page.next();
 }
 
-pub fn create_output_files(state: &mut AppState) {
+pub fn create_output_files(state: &mut AppState) -> Result<(), MappingError> {
     let output_format = state.mapping.output_format();
-    let json_value = state.repository.get_mut(&output_format).unwrap();
+    let json_value = state
+        .repository
+        .get(&output_format)
+        .ok_or_else(|| MappingError::UnknownFormat(output_format.clone()))?;
 
     // Create Output File
-    let mut file = std::fs::File::create(&state.output_path).unwrap();
-    file.write_all(serde_json::to_string_pretty(&json_value).unwrap().as_bytes())
-        .unwrap();
-
-    // Create Mapping File
-    let mut file = std::fs::File::create(&state.custom_mapping_path).unwrap();
-    file.write_all(serde_json::to_string_pretty(&state.mappings).unwrap().as_bytes())
-        .unwrap();
+    write_file(&state.output_path, json_value)?;
+
+    // Add the manual mappings to the custom mapping file, the top layer, where they replace the mappings of the same
+    // destinations. The lower layers are never written.
+    if state.custom_mapping_path.is_empty() {
+        return Ok(());
+    }
+    let mut mapping_file = if Path::new(&state.custom_mapping_path).is_file() {
+        MappingFile::read(&state.custom_mapping_path)?
+    } else {
+        MappingFile::new(&state.mapping.input_format(), &output_format, vec![])
+    };
+    let destinations = destinations(&state.mappings);
+    mapping_file.mappings.retain(|rule| !writes_any(rule, &destinations));
+    mapping_file.mappings.extend(state.mappings.clone());
+    mapping_file.touch();
+
+    write_file(&state.custom_mapping_path, &mapping_file)
+}
+
+/// Saves the session to the session file of P1, from which it can be resumed.
+pub fn save_session(state: &mut AppState) {
+    match Session::from(&*state).write(&state.session_path) {
+        Ok(()) => state.saved_mappings = serde_json::to_string(&state.mappings).unwrap_or_default(),
+        Err(e) => state.mapping_error = Some(e),
+    }
+}
+
+fn write_file(path: &str, value: &impl serde::Serialize) -> Result<(), MappingError> {
+    let invalid_file = |message: String| MappingError::InvalidFile {
+        path: path.to_string(),
+        message,
+    };
+
+    let mut file = std::fs::File::create(path).map_err(|e| invalid_file(e.to_string()))?;
+    let json = serde_json::to_string_pretty(value).map_err(|e| invalid_file(e.to_string()))?;
+    file.write_all(json.as_bytes()).map_err(|e| invalid_file(e.to_string()))
 }
 
 pub fn clear_mapping_options(state: &mut AppState) {
     state.select_mapping_option = true;
+    state.dividers.clear();
+    state.separator.clear();
+    state.constant_input.clear();
+    state.combiner = Combiners::Concat;
+    state.selected_input_fields.clear();
+    state.selected_missing_fields.clear();
+    state.selected_optional_fields.clear();
     state.selected_transformation = 0;
     state.selected_transformations.clear();
     state.selected_transformations_tab = false;
+    state.parameters_input = None;
+    state.second_parameter = false;
+    state.slice_input = Default::default();
+    state.regex_input = Default::default();
+    state.timezone_input.clear();
+    state.lookup_input = Default::default();
+    state.condition_input = Default::default();
     state.mapping_option = MappingOptions::DirectCopy;
     state.transformations = Transformations::LowerCase;
 }
@@ -415,6 +593,8 @@ pub fn clear_popup(state: &mut AppState) {
 }
 
 pub fn clear_button(state: &mut AppState) {
+    record(state, Action::Clear);
+
     // Close popup if open.
     if state.popup_mapping_p2_p3 {
         state.popup_mapping_p2_p3 = false;
@@ -423,18 +603,16 @@ pub fn clear_button(state: &mut AppState) {
     else if !state.select_mapping_option {
         clear_mapping_options(state);
     }
-    // Clear selected missing/optional field
+    // Clear selected missing/optional field and remove the mapping that writes it
     else {
-        if state.page == Pages::ManualMappingP2 {
-            state.missing_data_fields[state.selected_missing_field].1.clear();
-            state
-                .completed_missing_fields
-                .retain(|&(first, _)| first != state.selected_missing_field);
-        } else {
-            state.optional_fields[state.selected_optional_field].1.clear();
-            state
-                .completed_optional_fields
-                .retain(|&(first, _)| first != state.selected_optional_field);
+        let pointer = state.output_fields()[state.selected_output_field()].0.clone();
+        let destination = (state.mapping.output_format(), pointer);
+        state
+            .mappings
+            .retain(|rule| !writes_any(rule, std::slice::from_ref(&destination)));
+
+        if let Err(e) = replay_mappings(state) {
+            state.mapping_error = Some(e);
         }
 
         clear_mapping_options(state);
@@ -442,19 +620,40 @@ pub fn clear_button(state: &mut AppState) {
 }
 
 pub fn confirm_mapping(state: &mut AppState) {
+    record_if_applied(state, Action::Confirm, confirm);
+}
+
+/// Confirms the mapping that is filled in, returns whether a mapping was confirmed.
+fn confirm(state: &mut AppState) -> bool {
     clear_popup(state);
     state.p2_p3_tabs = P2P3Tabs::InputFields;
 
-    if state.page == Pages::ManualMappingP2 {
-        state.missing_data_fields[state.selected_missing_field].1 = state.candidate_data_value.clone().unwrap();
-    } else {
-        state.optional_fields[state.selected_optional_field].1 = state.candidate_data_value.clone().unwrap();
+    if state.mapping_option == MappingOptions::OneToMany && !state.select_mapping_option {
+        return confirm_one_to_many(state);
+    }
+    if state.mapping_option == MappingOptions::ManyToOne && !state.select_mapping_option {
+        return confirm_many_to_one(state);
+    }
+    if state.mapping_option == MappingOptions::Constant && !state.select_mapping_option {
+        return confirm_constant(state);
+    }
+    if state.selected_output_field() == 0 {
+        return false;
     }
 
-    trace_dbg!(state.candidate_data_value.as_ref().unwrap());
-    trace_dbg!(state.missing_data_fields.clone()[state.selected_missing_field].to_owned());
+    // Parameters can change after the last preview, so the transformations are applied once more to report errors
+    if let Err(e) = selector(state).and_then(|()| update_repository(state)) {
+        state.mapping_error = Some(e);
+        return false;
+    }
+    record_mapping(state, pipeline_transformation(state));
+
+    let candidate_data_value = state.candidate_data_value.clone().unwrap_or_default();
+    let field = state.selected_output_field();
+    state.output_fields_mut()[field].1 = candidate_data_value.clone();
 
-    update_repository(state);
+    trace_dbg!(&candidate_data_value);
+    trace_dbg!(state.output_fields()[field].to_owned());
 
     if state.page == Pages::ManualMappingP2 {
         // Save completed fields
@@ -515,4 +714,275 @@ pub fn confirm_mapping(state: &mut AppState) {
     }
 
     clear_mapping_options(state);
+    true
+}
+
+fn confirm_one_to_many(state: &mut AppState) -> bool {
+    if state.selected_output_fields().is_empty() {
+        return false;
+    }
+    let transformation = one_to_many_transformation(state);
+
+    if let Err(e) = state.repository.apply_transformation(transformation.clone()) {
+        state.mapping_error = Some(e);
+        return false;
+    }
+
+    // Show the parts in the output fields and mark the fields that received a part as completed
+    let output_format = state.mapping.output_format();
+    for field in state.selected_output_fields().clone() {
+        let Some(value) = state
+            .repository
+            .get(&output_format)
+            .and_then(|output_credential| output_credential.pointer(&state.output_fields()[field].0))
+            .map(|value| value.to_string())
+        else {
+            continue;
+        };
+
+        state.output_fields_mut()[field].1 = value;
+
+        let input_field = state.selected_input_field;
+        let completed_fields = state.completed_output_fields_mut();
+        completed_fields.retain(|&(first, _)| first != field);
+        completed_fields.push((field, input_field));
+    }
+
+    record_mapping(state, transformation);
+
+    clear_mapping_options(state);
+    true
+}
+
+/// Records the confirmed transformation as the mapping of its destinations. Mappings that write one of these
+/// destinations are replaced, the transformation takes the place of the first of them.
+fn record_mapping(state: &mut AppState, transformation: Transformation) {
+    let destinations = destinations(std::slice::from_ref(&transformation));
+
+    let Some(position) = state.mappings.iter().position(|rule| writes_any(rule, &destinations)) else {
+        state.mappings.push(transformation);
+        return;
+    };
+
+    state.mappings.retain(|rule| !writes_any(rule, &destinations));
+    state.mappings.insert(position, transformation);
+
+    // The replaced mappings may have written other destinations as well
+    if let Err(e) = replay_mappings(state) {
+        state.mapping_error = Some(e);
+    }
+}
+
+/// Applies the confirmed mappings to the repository as it was loaded, and shows the values they write in the output
+/// fields. Nothing changes when a mapping can't be applied.
+pub fn replay_mappings(state: &mut AppState) -> Result<(), MappingError> {
+    // Mappings whose source has no value are skipped, as when the mapping is converted
+    let mut repository = Repository::from(state.loaded_repository.clone());
+    let warnings = repository.apply_transformations(state.mappings.clone())?;
+    trace_dbg!(&warnings);
+    state.repository = repository;
+
+    let output_format = state.mapping.output_format();
+    let output_credential = state.repository.get(&output_format).cloned().unwrap_or_default();
+
+    // The input fields the last mapping of an output field reads, or the empty field for a constant
+    let input_fields_of = |pointer: &str| {
+        let transformation = state.mappings.iter().rev().find(|rule| {
+            rule.destinations()
+                .iter()
+                .any(|destination| destination.format == output_format && normalize(&destination.path) == pointer)
+        })?;
+        let input_fields: Vec<usize> = transformation
+            .sources()
+            .iter()
+            .filter_map(|source| JsonPointer::try_from(JsonPath(source.path.clone())).ok())
+            .filter_map(|source| state.input_fields.iter().position(|(pointer, _)| *pointer == *source))
+            .collect();
+
+        Some(if input_fields.is_empty() { vec![0] } else { input_fields })
+    };
+
+    for (fields, completed_fields) in [
+        (&mut state.missing_data_fields, &mut state.completed_missing_fields),
+        (&mut state.optional_fields, &mut state.completed_optional_fields),
+    ] {
+        completed_fields.clear();
+        for (field, (pointer, value)) in fields.iter_mut().enumerate().skip(1) {
+            // Fields that are not mapped keep the value of the mapping files, if any
+            *value = output_credential
+                .pointer(pointer)
+                .map(Value::to_string)
+                .unwrap_or_default();
+            if let Some(input_fields) = input_fields_of(pointer) {
+                completed_fields.extend(input_fields.into_iter().map(|input_field| (field, input_field)));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the parameter of the transformation that is typed into.
+fn active_parameter(state: &mut AppState) -> &mut String {
+    let parameters = match state.parameters_input {
+        Some(Transformations::Regex) => &mut state.regex_input,
+        Some(Transformations::Lookup) => &mut state.lookup_input,
+        Some(Transformations::Condition) => &mut state.condition_input,
+        Some(Transformations::DateTime) => return &mut state.timezone_input,
+        _ => &mut state.slice_input,
+    };
+
+    if state.second_parameter {
+        &mut parameters.1
+    } else {
+        &mut parameters.0
+    }
+}
+
+/// Adds the active output field as the next destination of the OneToMany mapping, or removes it when it was selected
+/// already.
+fn toggle_destination(state: &mut AppState) {
+    record(state, Action::SelectField);
+    let field = state.selected_output_field();
+
+    let destinations = state.selected_output_fields_mut();
+    if let Some(position) = destinations.iter().position(|&destination| destination == field) {
+        destinations.remove(position);
+    } else if field != 0 {
+        destinations.push(field);
+    }
+}
+
+fn confirm_many_to_one(state: &mut AppState) -> bool {
+    let field = state.selected_output_field();
+    if field == 0 || state.selected_input_fields.is_empty() {
+        return false;
+    }
+
+    let transformation = many_to_one_transformation(state);
+
+    if let Err(e) = state.repository.apply_transformation(transformation.clone()) {
+        state.mapping_error = Some(e);
+        return false;
+    }
+
+    // Show the combined value in the output field and mark all combined input fields as used for it
+    let output_format = state.mapping.output_format();
+    let value = state
+        .repository
+        .get(&output_format)
+        .and_then(|output_credential| output_credential.pointer(&state.output_fields()[field].0))
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+    state.output_fields_mut()[field].1 = value;
+
+    let input_fields = state.selected_input_fields.clone();
+    let completed_fields = state.completed_output_fields_mut();
+    completed_fields.retain(|&(first, _)| first != field);
+    completed_fields.extend(input_fields.into_iter().map(|input_field| (field, input_field)));
+
+    record_mapping(state, transformation);
+
+    // Move active field to next field
+    if field == state.output_fields().len() - 1 {
+        *state.selected_output_field_mut() = 1;
+    } else {
+        *state.selected_output_field_mut() += 1;
+    }
+
+    clear_mapping_options(state);
+    true
+}
+
+fn confirm_constant(state: &mut AppState) -> bool {
+    let field = state.selected_output_field();
+    if field == 0 {
+        return false;
+    }
+
+    let transformation = constant_transformation(state);
+
+    if let Err(e) = state.repository.apply_transformation(transformation.clone()) {
+        state.mapping_error = Some(e);
+        return false;
+    }
+
+    // Show the value in the output field and mark it as completed, without an input field
+    let output_format = state.mapping.output_format();
+    let value = state
+        .repository
+        .get(&output_format)
+        .and_then(|output_credential| output_credential.pointer(&state.output_fields()[field].0))
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+    state.output_fields_mut()[field].1 = value;
+
+    let completed_fields = state.completed_output_fields_mut();
+    completed_fields.retain(|&(first, _)| first != field);
+    completed_fields.push((field, 0));
+
+    record_mapping(state, transformation);
+
+    // Move active field to next field
+    if field == state.output_fields().len() - 1 {
+        *state.selected_output_field_mut() = 1;
+    } else {
+        *state.selected_output_field_mut() += 1;
+    }
+
+    clear_mapping_options(state);
+    true
+}
+
+/// Adds the active input field as the next source of the ManyToOne mapping, or removes it when it was selected already.
+fn toggle_source(state: &mut AppState) {
+    record(state, Action::SelectField);
+    let field = state.selected_input_field;
+
+    if let Some(position) = state.selected_input_fields.iter().position(|&source| source == field) {
+        state.selected_input_fields.remove(position);
+    } else if field != 0 {
+        state.selected_input_fields.push(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn field(pointer: &str, value: &str) -> (String, String) {
+        (pointer.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn confirming_on_p3_maps_the_optional_field() {
+        let mut state = AppState {
+            page: Pages::UnusedDataP3,
+            input_fields: vec![field("", ""), field("/title", "\"Rust\"")],
+            selected_input_field: 1,
+            missing_data_fields: vec![field("", ""), field("/id", "")],
+            selected_missing_field: 1,
+            optional_fields: vec![field("", ""), field("/name", "")],
+            selected_optional_field: 1,
+            repository: Repository::from(HashMap::from_iter([
+                ("ELM".to_string(), json!({ "title": "Rust" })),
+                ("OBv3".to_string(), json!({})),
+            ])),
+            ..Default::default()
+        };
+
+        confirm_mapping(&mut state);
+
+        assert_eq!(state.mapping_error, None);
+        assert_eq!(
+            destinations(&state.mappings),
+            [("OBv3".to_string(), "/name".to_string())]
+        );
+        assert_eq!(state.repository["OBv3"], json!({ "name": "Rust" }));
+        assert_eq!(state.optional_fields[1].1, "\"Rust\"");
+        assert_eq!(state.completed_optional_fields, [(1, 1)]);
+        assert!(state.completed_missing_fields.is_empty());
+    }
 }
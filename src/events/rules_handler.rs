@@ -0,0 +1,182 @@
+use crossterm::event::{self, Event, KeyCode::*, KeyEvent, KeyEventKind, KeyModifiers};
+use std::path::Path;
+
+use super::is_mouse_over_area;
+use crate::{
+    backend::{error::MappingError, session::discard_autosave, transformations::Transformation},
+    p2_p3_common::{create_output_files, replay_mappings, save_session},
+    state::AppState,
+};
+
+pub fn rules_handler(event: Event, state: &mut AppState) -> Result<bool, std::io::Error> {
+    if let event::Event::Key(key) = event {
+        if key.kind == KeyEventKind::Press && state.rule_input.is_some() {
+            handle_rule_input(state, key);
+        } else if key.kind == KeyEventKind::Press {
+            let last_rule = state.mappings.len().saturating_sub(1);
+            let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+
+            match key.code {
+                Esc => {
+                    state.exit_warning = !state.exit_warning;
+                }
+                Enter => {
+                    if state.exit_warning {
+                        return Ok(true);
+                    }
+                    edit_rule(state);
+                }
+                // Move the selected rule, rules are applied from top to bottom
+                Up if shift && state.selected_rule > 0 => {
+                    let rule = state.selected_rule;
+                    change_rules(state, |rules| rules.swap(rule, rule - 1));
+                    state.selected_rule -= 1;
+                }
+                Down if shift && state.selected_rule < last_rule => {
+                    let rule = state.selected_rule;
+                    change_rules(state, |rules| rules.swap(rule, rule + 1));
+                    state.selected_rule += 1;
+                }
+                Up => {
+                    state.selected_rule = state.selected_rule.saturating_sub(1);
+                }
+                Down => {
+                    state.selected_rule = (state.selected_rule + 1).min(last_rule);
+                }
+                Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    save_session(state);
+                }
+                Delete | Backspace if !state.mappings.is_empty() => {
+                    let rule = state.selected_rule;
+                    change_rules(state, |rules| {
+                        rules.remove(rule);
+                    });
+                    state.selected_rule = state.selected_rule.min(state.mappings.len().saturating_sub(1));
+                }
+                _ => {}
+            }
+        }
+    }
+    if let event::Event::Mouse(mouse_event) = event {
+        match mouse_event.kind {
+            event::MouseEventKind::ScrollDown => {
+                state.selected_rule = (state.selected_rule + 1).min(state.mappings.len().saturating_sub(1));
+            }
+            event::MouseEventKind::ScrollUp => {
+                state.selected_rule = state.selected_rule.saturating_sub(1);
+            }
+            event::MouseEventKind::Up(_) => {
+                if is_mouse_over_area(state.complete_button, mouse_event.column, mouse_event.row) {
+                    match create_output_files(state) {
+                        Ok(()) => {
+                            discard_autosave();
+                            state.page.next();
+                        }
+                        Err(e) => state.mapping_error = Some(e),
+                    }
+                } else if is_mouse_over_area(state.prev_page_button, mouse_event.column, mouse_event.row) {
+                    state.rule_input = None;
+                    state.page.prev();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(false)
+}
+
+////////////     HELPERS     ////////////
+
+/// Opens the selected rule as JSON, with the cursor at the end.
+fn edit_rule(state: &mut AppState) {
+    let Some(rule) = state.mappings.get(state.selected_rule) else {
+        return;
+    };
+    let Ok(json) = serde_json::to_string(rule) else {
+        return;
+    };
+
+    state.rule_cursor = json.chars().count();
+    state.rule_input = Some(json);
+}
+
+fn handle_rule_input(state: &mut AppState, key: KeyEvent) {
+    let Some(input) = state.rule_input.as_mut() else {
+        return;
+    };
+    // The cursor counts characters, the input is edited at byte positions
+    let position = |input: &str, cursor: usize| input.char_indices().nth(cursor).map_or(input.len(), |(i, _)| i);
+
+    match key.code {
+        Esc => {
+            state.rule_input = None;
+        }
+        Enter => save_rule(state),
+        Left => {
+            state.rule_cursor = state.rule_cursor.saturating_sub(1);
+        }
+        Right => {
+            state.rule_cursor = (state.rule_cursor + 1).min(input.chars().count());
+        }
+        Home => {
+            state.rule_cursor = 0;
+        }
+        End => {
+            state.rule_cursor = input.chars().count();
+        }
+        Backspace if state.rule_cursor > 0 => {
+            state.rule_cursor -= 1;
+            input.remove(position(input, state.rule_cursor));
+        }
+        Delete if state.rule_cursor < input.chars().count() => {
+            input.remove(position(input, state.rule_cursor));
+        }
+        Char(c) => {
+            input.insert(position(input, state.rule_cursor), c);
+            state.rule_cursor += 1;
+        }
+        _ => {}
+    }
+}
+
+/// Replaces the selected rule with the edited JSON. Invalid JSON and lookup tables that can't be loaded are reported and
+/// stay open for editing.
+fn save_rule(state: &mut AppState) {
+    let Some(input) = &state.rule_input else {
+        return;
+    };
+
+    // The lookup tables are relative to the custom mapping file the rules are saved to, as when it is loaded again
+    let base = Path::new(&state.custom_mapping_path).parent().unwrap_or(Path::new(""));
+    let parsed = serde_json::from_str::<Transformation>(input)
+        .map_err(|e| MappingError::InvalidTransformation(e.to_string()))
+        .and_then(|mut transformation| {
+            transformation.load_tables(base)?;
+            Ok(transformation)
+        });
+
+    match parsed {
+        Ok(transformation) => {
+            let rule = state.selected_rule;
+            state.rule_input = None;
+            change_rules(state, |rules| rules[rule] = transformation);
+        }
+        Err(e) => state.mapping_error = Some(e),
+    }
+}
+
+/// Changes the confirmed rules and applies them again. The change is undone when the rules can't be applied.
+fn change_rules(state: &mut AppState, change: impl FnOnce(&mut Vec<Transformation>)) {
+    let previous = state.mappings.clone();
+    change(&mut state.mappings);
+
+    match replay_mappings(state) {
+        // The snapshots of P2 and P3 don't know about the changed rules
+        Ok(()) => state.history.clear(),
+        Err(e) => {
+            state.mappings = previous;
+            state.mapping_error = Some(e);
+        }
+    }
+}
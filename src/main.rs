@@ -1,4 +1,5 @@
 mod backend;
+mod cli;
 mod events;
 mod render;
 mod state;
@@ -7,11 +8,17 @@ mod translations;
 use crate::events::*;
 use crate::render::*;
 
-use backend::logging::initialize_logging;
+use backend::{
+    logging::initialize_logging,
+    session::{autosave, autosave_on_exit, autosave_path, Session},
+};
+use clap::Parser;
+use cli::Cli;
 use crossterm::event::DisableMouseCapture;
 use crossterm::event::EnableMouseCapture;
 use crossterm::execute;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use events::p1_handler::resume_session;
 use ratatui::prelude::{CrosstermBackend, Terminal};
 use state::AppState;
 use std::io::{stdout, Result};
@@ -25,23 +32,25 @@ fn main() -> Result<()> {
     initialize_logging().expect("Unexpected error while initializing logging");
     trace_dbg!("Starting the application");
 
-    // Initialize the alternate terminal screen, its input and the backend for it.
-    execute!(stdout(), EnterAlternateScreen, EnableMouseCapture)?;
-    enable_raw_mode()?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
-    terminal.clear()?;
+    // Run a command without the terminal interface if one is given.
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        std::process::exit(command.run());
+    }
+
     let mut state = AppState {
         // Default example values, remove if no longer needed
         input_path: "res/elm_example.json".to_string(),
-        mapping_path: "res/mapping_empty.json".to_string(),
+        mapping_paths: vec!["res/mapping_empty.json".to_string()],
         output_path: "res/output_credential.json".to_string(),
-        custom_mapping_path: "res/custom_mapping.json".to_string(),
+        custom_mapping_path: String::new(), // Only a custom mapping file that is given is loaded and saved to
+        session_path: "res/session.json".to_string(),
 
         optional_fields: vec![
             ("".to_string(), "".to_string()),
-            ("credentialSubject/e-Mail".to_string(), "".to_string()),
-            ("credentialSubject/phoneNumber".to_string(), "".to_string()),
-            ("credentialSubject/gender".to_string(), "".to_string()),
+            ("/credentialSubject/e-Mail".to_string(), "".to_string()),
+            ("/credentialSubject/phoneNumber".to_string(), "".to_string()),
+            ("/credentialSubject/gender".to_string(), "".to_string()),
         ], // todo: load in optional fields properly
 
         selected_input_field: 1, // todo: what if none? Also after going back to tab 1 and changing file paths?
@@ -51,6 +60,30 @@ fn main() -> Result<()> {
         ..Default::default()
     };
 
+    // Resume the session given on the command line, or offer to recover the autosaved session.
+    if let Some(path) = cli.resume {
+        if let Err(e) = resume_session(&mut state, path) {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    } else if let Ok(session) = Session::read(autosave_path()) {
+        state.recovery_warning = Some(session.saved);
+    }
+
+    // Restore the terminal before printing the panic message, otherwise it stays in raw mode.
+    let panic_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        let _ = execute!(stdout(), LeaveAlternateScreen, DisableMouseCapture);
+        let _ = disable_raw_mode();
+        panic_hook(panic_info);
+    }));
+
+    // Initialize the alternate terminal screen, its input and the backend for it.
+    execute!(stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    enable_raw_mode()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    terminal.clear()?;
+
     loop {
         terminal.draw(|frame| {
             let area = frame.size();
@@ -61,8 +94,12 @@ fn main() -> Result<()> {
         if events_handler(&mut state)? {
             break;
         };
+        autosave(&mut state);
     }
 
+    // Save the latest changes, so they can be recovered after quitting halfway
+    autosave_on_exit(&mut state);
+
     execute!(stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
     disable_raw_mode()?;
     Ok(())
@@ -1,4 +1,4 @@
-use crate::state::{translate, AppState, MappingOptions, P2P3Tabs};
+use crate::state::{translate, AppState, MappingOptions, P2P3Tabs, Transformations};
 use ratatui::{
     buffer::Buffer,
     layout::{Constraint, Rect},
@@ -15,6 +15,7 @@ pub fn render_mapping_bar(bottom: Rect, buf: &mut Buffer, state: &mut AppState,
             translate("transformations").to_string(),
             translate("one_to_many").to_string(),
             translate("many_to_one").to_string(),
+            translate("constant").to_string(),
         ];
         let clear_len = format!(" {} ", translate("clear")).chars().count() as u16;
         let view_len = format!(" {} ", translate("view")).chars().count() as u16;
@@ -36,9 +37,13 @@ pub fn render_mapping_bar(bottom: Rect, buf: &mut Buffer, state: &mut AppState,
         render_mapping_bar_buttons(clear, view, state, buf);
     } else {
         match state.mapping_option {
+            MappingOptions::Transformations if state.parameters_input.is_some() => {
+                render_parameters_bar(bottom, buf, state)
+            }
             MappingOptions::Transformations => render_transformations_bar(bottom, buf, state),
             MappingOptions::OneToMany => render_onetomany_bar(bottom, buf, state),
             MappingOptions::ManyToOne => render_manytoone_bar(bottom, buf, state),
+            MappingOptions::Constant => render_constant_bar(bottom, buf, state),
             MappingOptions::DirectCopy => {}
         }
     }
@@ -51,6 +56,15 @@ pub fn render_transformations_bar(area: Rect, buf: &mut Buffer, state: &mut AppS
         translate("uppercase").to_string(),
         translate("slice").to_string(),
         "Regex".to_string(),
+        translate("trim").to_string(),
+        translate("datetime").to_string(),
+        translate("lookup").to_string(),
+        translate("text").to_string(),
+        translate("number").to_string(),
+        translate("boolean").to_string(),
+        translate("wrap").to_string(),
+        translate("unwrap").to_string(),
+        translate("condition").to_string(),
     ];
     let clear_len = format!(" {} ", translate("clear")).chars().count() as u16;
     let view_len = format!(" {} ", translate("view")).chars().count() as u16;
@@ -139,7 +153,8 @@ pub fn render_onetomany_bar(area: Rect, buf: &mut Buffer, state: &mut AppState)
             )
             .render(dividers, buf);
     } else {
-        Paragraph::new(" ".to_owned() + state.dividers.as_str())
+        // Show the dividers quoted, so spaces are visible as well
+        Paragraph::new(format!(" {:?}", state.dividers))
             .style(Style::default().fg(Color::Black).bg(Color::Gray))
             .render(dividers, buf);
     }
@@ -150,11 +165,14 @@ pub fn render_onetomany_bar(area: Rect, buf: &mut Buffer, state: &mut AppState)
 pub fn render_manytoone_bar(area: Rect, buf: &mut Buffer, state: &mut AppState) {
     // Get the translation first to calculate how much space the texts need
     let txt = format!("  {} ", translate("select_fields"));
+    let combiners = [format!(" {}", translate("concat")), translate("array").to_string()];
     let clear_len = format!(" {} ", translate("clear")).chars().count() as u16;
     let view_len = format!(" {} ", translate("view")).chars().count() as u16;
 
-    let [txt_area, clear, view] = Layout::horizontal(vec![
-        Constraint::Min(txt.len() as u16 + 2),
+    let [txt_area, combiners_area, separator, clear, view] = Layout::horizontal(vec![
+        Constraint::Min(txt.chars().count() as u16),
+        Constraint::Length(combiners.concat().chars().count() as u16 + 4),
+        Constraint::Percentage(100),
         Constraint::Length(clear_len),
         Constraint::Length(view_len),
     ])
@@ -169,6 +187,65 @@ pub fn render_manytoone_bar(area: Rect, buf: &mut Buffer, state: &mut AppState)
         )
         .render(txt_area, buf);
 
+    let mut active_style = Style::default().fg(Color::White).bg(Color::DarkGray);
+    if state.p2_p3_tabs == P2P3Tabs::MappingOptions {
+        active_style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    }
+
+    Tabs::new(combiners)
+        .style(Style::default().fg(Color::White).bg(Color::DarkGray))
+        .highlight_style(active_style)
+        .select(state.combiner as usize)
+        .divider("")
+        .render(combiners_area, buf);
+
+    // Display italic instructions to be overwritten by user input for the separator.
+    if state.separator.is_empty() {
+        let txt = format!(" {} ", translate("enter_separator"));
+        Paragraph::new(txt)
+            .style(
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Gray)
+                    .add_modifier(Modifier::ITALIC),
+            )
+            .render(separator, buf);
+    } else {
+        // Show the separator quoted, so spaces are visible as well
+        Paragraph::new(format!(" {:?}", state.separator))
+            .style(Style::default().fg(Color::Black).bg(Color::Gray))
+            .render(separator, buf);
+    }
+
+    render_mapping_bar_buttons(clear, view, state, buf);
+}
+
+pub fn render_constant_bar(area: Rect, buf: &mut Buffer, state: &mut AppState) {
+    // Get the translation first to calculate how much space the texts need
+    let txt = format!("  {} ", translate("enter_constant"));
+    let clear_len = format!(" {} ", translate("clear")).chars().count() as u16;
+    let view_len = format!(" {} ", translate("view")).chars().count() as u16;
+
+    let [txt_area, constant, clear, view] = Layout::horizontal(vec![
+        Constraint::Min(txt.chars().count() as u16),
+        Constraint::Percentage(100),
+        Constraint::Length(clear_len),
+        Constraint::Length(view_len),
+    ])
+    .areas(area);
+
+    Paragraph::new(txt)
+        .style(Style::default().fg(Color::White).bg(Color::DarkGray))
+        .render(txt_area, buf);
+
+    let mut style = Style::default().fg(Color::Black).bg(Color::Gray);
+    if state.p2_p3_tabs == P2P3Tabs::MappingOptions {
+        style = style.bg(Color::Yellow);
+    }
+    Paragraph::new(format!(" {}", state.constant_input))
+        .style(style)
+        .render(constant, buf);
+
     render_mapping_bar_buttons(clear, view, state, buf);
 }
 
@@ -195,23 +272,84 @@ pub fn render_mapping_bar_buttons(clear: Rect, view: Rect, state: &mut AppState,
     Paragraph::new(view_txt).style(view_style).render(view, buf);
 }
 
-// pub fn render_slice_bar(area: Rect, buf: &mut Buffer, state: &mut AppState) {
-//     let txt = "  Enter two dividers as start and end of the slice";
-//     let [txt_area, dividers, clear, view] = Layout::horizontal(vec![
-//         Constraint::Min(txt.len() as u16),
-//         Constraint::Percentage(100),
-//         Constraint::Length(7),
-//         Constraint::Length(6),
-//     ])
-//     .areas(area);
-
-//     Paragraph::new(txt)
-//         .style(Style::default().fg(Color::White).bg(Color::DarkGray))
-//         .render(txt_area, buf);
-
-//     Paragraph::new(" ".to_owned() + state.slice_input.0.as_str() + " --> " + state.slice_input.1.as_str())
-//         .style(Style::default().fg(Color::Black).bg(Color::Gray))
-//         .render(dividers, buf);
-
-//     render_mapping_bar_buttons(clear, view, state, buf);
-// }
+pub fn render_parameters_bar(area: Rect, buf: &mut Buffer, state: &mut AppState) {
+    // Get the translation first to calculate how much space the texts need
+    let (txt, inputs) = match state.parameters_input {
+        Some(Transformations::Regex) => (
+            format!("  {} ", translate("enter_regex")),
+            vec![
+                (state.regex_input.0.clone(), translate("pattern")),
+                (state.regex_input.1.clone(), translate("replacement")),
+            ],
+        ),
+        Some(Transformations::Lookup) => (
+            format!("  {} ", translate("enter_lookup")),
+            vec![
+                (state.lookup_input.0.clone(), translate("table")),
+                (state.lookup_input.1.clone(), translate("default")),
+            ],
+        ),
+        Some(Transformations::Condition) => (
+            format!("  {} ", translate("enter_condition")),
+            vec![
+                (state.condition_input.0.clone(), translate("path")),
+                (state.condition_input.1.clone(), translate("expected")),
+            ],
+        ),
+        Some(Transformations::DateTime) => (
+            format!("  {} ", translate("enter_timezone")),
+            vec![(state.timezone_input.clone(), translate("timezone"))],
+        ),
+        _ => (
+            format!("  {} ", translate("enter_slice")),
+            vec![
+                (state.slice_input.0.clone(), translate("start")),
+                (state.slice_input.1.clone(), translate("end")),
+            ],
+        ),
+    };
+    let clear_len = format!(" {} ", translate("clear")).chars().count() as u16;
+    let view_len = format!(" {} ", translate("view")).chars().count() as u16;
+
+    // The inputs share the space, separated by arrows
+    let mut constraints = vec![Constraint::Min(txt.chars().count() as u16)];
+    for index in 0..inputs.len() {
+        if index > 0 {
+            constraints.push(Constraint::Length(5));
+        }
+        constraints.push(Constraint::Percentage(100 / inputs.len() as u16));
+    }
+    constraints.extend([Constraint::Length(clear_len), Constraint::Length(view_len)]);
+    let areas = Layout::horizontal(constraints).split(area);
+
+    Paragraph::new(txt)
+        .style(Style::default().fg(Color::White).bg(Color::DarkGray))
+        .render(areas[0], buf);
+
+    // The parameter that is typed into is highlighted, empty parameters show their name in italic.
+    let active = usize::from(state.second_parameter).min(inputs.len() - 1);
+    for (index, (input, placeholder)) in inputs.into_iter().enumerate() {
+        if index > 0 {
+            Paragraph::new(" --> ")
+                .style(Style::default().fg(Color::White).bg(Color::DarkGray))
+                .render(areas[2 * index], buf);
+        }
+
+        let mut style = Style::default().fg(Color::Black).bg(Color::Gray);
+        if state.p2_p3_tabs == P2P3Tabs::MappingOptions && index == active {
+            style = style.bg(Color::Yellow);
+        }
+
+        if input.is_empty() {
+            Paragraph::new(format!(" {placeholder} "))
+                .style(style.add_modifier(Modifier::ITALIC))
+                .render(areas[2 * index + 1], buf);
+        } else {
+            Paragraph::new(format!(" {input}"))
+                .style(style)
+                .render(areas[2 * index + 1], buf);
+        }
+    }
+
+    render_mapping_bar_buttons(areas[areas.len() - 2], areas[areas.len() - 1], state, buf);
+}
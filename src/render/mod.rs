@@ -4,17 +4,20 @@ pub mod p2;
 pub mod p3;
 pub mod p4;
 pub mod popups;
+pub mod rules;
 
 use p1::render_description_input_p1;
 use p2::render_manual_mapping_p2;
 use p3::render_lost_data_p3;
 use p4::render_end_p4;
+use popups::render_popup_mapping_error;
 use ratatui::prelude::*;
 use ratatui::{
     buffer::Buffer,
     layout::{Constraint, Rect},
     widgets::{Block, Clear},
 };
+use rules::render_mapping_rules_p4;
 
 use crate::state::{translate, AppState, Pages};
 
@@ -25,7 +28,8 @@ pub fn render_page(frame: &mut Frame, area: Rect, state: &mut AppState) {
         Pages::InputPromptsP1 => render_description_input_p1(top, frame.buffer_mut(), state),
         Pages::ManualMappingP2 => render_manual_mapping_p2(top, frame.buffer_mut(), state),
         Pages::UnusedDataP3 => render_lost_data_p3(top, frame.buffer_mut(), state),
-        Pages::EndP4 => render_end_p4(top, frame.buffer_mut(), state),
+        Pages::MappingRulesP4 => render_mapping_rules_p4(top, frame.buffer_mut(), state),
+        Pages::EndP5 => render_end_p4(top, frame.buffer_mut(), state),
     }
 
     // Extra layout for the prev page & finish button
@@ -48,10 +52,15 @@ pub fn render_page(frame: &mut Frame, area: Rect, state: &mut AppState) {
     state.complete_button = complete_button;
     render_complete_button(complete_button, frame.buffer_mut());
 
-    render_bottom_bar(bottom_area, frame.buffer_mut());
+    render_bottom_bar(bottom_area, frame.buffer_mut(), state.page);
+
+    // Render the error popup last so it is shown on top of everything else.
+    if let Some(error) = &state.mapping_error {
+        render_popup_mapping_error(top, frame.buffer_mut(), error);
+    }
 }
 
-fn render_bottom_bar(area: Rect, buf: &mut Buffer) {
+fn render_bottom_bar(area: Rect, buf: &mut Buffer, page: Pages) {
     let vertical_sections = Layout::horizontal(vec![Constraint::Length(23), Constraint::Min(0)]);
     let [left, right] = vertical_sections.areas(area);
 
@@ -62,13 +71,23 @@ fn render_bottom_bar(area: Rect, buf: &mut Buffer) {
         .bg(Color::Black)
         .render(left, buf);
 
-    let keys = [
+    let mut keys = vec![
         ("←↓↑→", translate("navigate")),
         ("Tab", translate("next_field")),
         ("F2", translate("prev_field")),
         ("Enter", translate("save")),
-        ("Esc", translate("quit")),
     ];
+    if matches!(page, Pages::ManualMappingP2 | Pages::UnusedDataP3) {
+        keys.push(("Ctrl+Z", translate("undo")));
+        keys.push(("Ctrl+Y", translate("redo")));
+    }
+    if matches!(
+        page,
+        Pages::ManualMappingP2 | Pages::UnusedDataP3 | Pages::MappingRulesP4
+    ) {
+        keys.push(("Ctrl+S", translate("save_session")));
+    }
+    keys.push(("Esc", translate("quit")));
 
     let spans: Vec<Span> = keys
         .iter()
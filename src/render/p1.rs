@@ -1,5 +1,8 @@
 use crate::{
-    popups::{render_popup_exit_warning, render_popup_overwrite_warning},
+    popups::{
+        render_popup_exit_warning, render_popup_lint_warning, render_popup_overwrite_warning,
+        render_popup_recovery_warning,
+    },
     state::{translate, AppState, P1Prompts},
     trace_dbg,
 };
@@ -43,8 +46,9 @@ pub fn render_description_input_p1(area: Rect, buf: &mut Buffer, state: &mut App
         Constraint::Length(4),
         Constraint::Length(4),
         Constraint::Length(4),
+        Constraint::Length(4),
     ]);
-    let [input_path, output_path, mapping_file, mapping, custom_mapping] = input_prompts.areas(prompts_area);
+    let [input_path, output_path, mapping_file, mapping, custom_mapping, session] = input_prompts.areas(prompts_area);
 
     let mut input_prompt = Block::new()
         .title(format!("  {}  ", translate("input_path")))
@@ -59,11 +63,15 @@ pub fn render_description_input_p1(area: Rect, buf: &mut Buffer, state: &mut App
         .title_alignment(Alignment::Center)
         .borders(Borders::ALL);
     let mut mapping_file_prompt = Block::new()
-        .title(format!("  {}  ", translate("choose_mapping_file")))
+        .title(format!("  {}  ", translate("choose_mapping_files")))
         .title_alignment(Alignment::Center)
         .borders(Borders::ALL);
     let mut custom_mapping_prompt = Block::new()
-        .title(format!("  {}  ", translate("save_custom_mapping")))
+        .title(format!("  {}  ", translate("custom_mapping_layer")))
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL);
+    let mut session_prompt = Block::new()
+        .title(format!("  {}  ", translate("session_file")))
         .title_alignment(Alignment::Center)
         .borders(Borders::ALL);
     // Top-left language prompt
@@ -80,6 +88,7 @@ pub fn render_description_input_p1(area: Rect, buf: &mut Buffer, state: &mut App
         P1Prompts::Mapping => mapping_prompt = mapping_prompt.style(active_style),
         P1Prompts::MappingFile => mapping_file_prompt = mapping_file_prompt.style(active_style),
         P1Prompts::CustomMapping => custom_mapping_prompt = custom_mapping_prompt.style(active_style),
+        P1Prompts::Session => session_prompt = session_prompt.style(active_style),
     };
 
     // Checking paths for validity/overwriting.
@@ -115,14 +124,13 @@ pub fn render_description_input_p1(area: Rect, buf: &mut Buffer, state: &mut App
             .render(output_path, buf);
     }
 
-    let path = Path::new(&state.mapping_path);
-    if !path.exists() || !path.is_file() {
-        Paragraph::new(state.mapping_path.as_str())
+    if !state.mapping_files_exist() {
+        Paragraph::new(state.mapping_paths.join(", "))
             .block(mapping_file_prompt)
             .fg(Color::Red)
             .render(mapping_file, buf);
     } else {
-        Paragraph::new(state.mapping_path.as_str())
+        Paragraph::new(state.mapping_paths.join(", "))
             .block(mapping_file_prompt)
             .fg(Color::Green)
             .render(mapping_file, buf);
@@ -135,7 +143,12 @@ pub fn render_description_input_p1(area: Rect, buf: &mut Buffer, state: &mut App
         horizontal: 0,
     });
 
-    let tabs = vec![" OBv3 -> ELM ", " ELM -> OBv3 "];
+    let tabs = vec![
+        " OBv3 -> ELM ",
+        " ELM -> OBv3 ",
+        " OBv3 -> spine -> ELM ",
+        " ELM -> spine -> OBv3 ",
+    ];
     let [_left, tabs_center, _right] = Layout::horizontal(vec![
         Constraint::Min(1),
         Constraint::Max(tabs.concat().len() as u16 + 2),
@@ -150,18 +163,12 @@ pub fn render_description_input_p1(area: Rect, buf: &mut Buffer, state: &mut App
         .divider("")
         .render(tabs_center, buf);
 
-    // Custom mapping prompt
-    let path = Path::new(&state.custom_mapping_path);
+    // Custom mapping prompt, an existing file is loaded as the top layer and the new mappings are added to it
     if state.custom_mapping_path.is_empty() {
         Paragraph::new(state.custom_mapping_path.as_str())
             .block(custom_mapping_prompt)
             .fg(Color::White)
             .render(custom_mapping, buf);
-    } else if path.is_file() {
-        Paragraph::new(state.custom_mapping_path.as_str())
-            .block(custom_mapping_prompt)
-            .fg(Color::Rgb(240, 160, 100))
-            .render(custom_mapping, buf);
     } else {
         Paragraph::new(state.custom_mapping_path.as_str())
             .block(custom_mapping_prompt)
@@ -169,6 +176,20 @@ pub fn render_description_input_p1(area: Rect, buf: &mut Buffer, state: &mut App
             .render(custom_mapping, buf);
     }
 
+    // Session prompt, a session can be resumed when its file exists
+    let path = Path::new(&state.session_path);
+    if path.is_file() {
+        Paragraph::new(state.session_path.as_str())
+            .block(session_prompt)
+            .fg(Color::Green)
+            .render(session, buf);
+    } else {
+        Paragraph::new(state.session_path.as_str())
+            .block(session_prompt)
+            .fg(Color::White)
+            .render(session, buf);
+    }
+
     // Top-left language prompt
     language_prompt.render(languages_area, buf);
     let language_prompt_inner = languages_area.inner(&Margin {
@@ -218,6 +239,14 @@ pub fn render_description_input_p1(area: Rect, buf: &mut Buffer, state: &mut App
     if state.overwrite_warning {
         render_popup_overwrite_warning(area, buf);
     }
+    // Render the findings of the linter before the mapping is loaded.
+    if !state.lint_warning.is_empty() {
+        render_popup_lint_warning(area, buf, &state.lint_warning);
+    }
+    // Render the offer to recover the autosaved session.
+    if let Some(saved) = &state.recovery_warning {
+        render_popup_recovery_warning(area, buf, saved);
+    }
     // Render warning if user wants to exit.
     if state.exit_warning {
         render_popup_exit_warning(area, buf);
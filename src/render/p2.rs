@@ -1,5 +1,6 @@
 use crate::{
-    mapping_bars::{render_manytoone_bar, render_mapping_bar},
+    backend::layers::layer_name,
+    mapping_bars::render_mapping_bar,
     popups::{render_popup_exit_warning, render_popup_mapping, render_popup_uncompleted_warning_p2},
     state::{translate, AppState, MappingOptions, P2P3Tabs},
 };
@@ -65,6 +66,11 @@ pub fn render_manual_mapping_p2(area: Rect, buf: &mut Buffer, state: &mut AppSta
         .iter()
         .enumerate()
         .map(|(index, (key, value))| {
+            // Number the sources of a ManyToOne mapping in the order they are combined
+            if let Some(position) = state.selected_input_fields.iter().position(|&field| field == index) {
+                return Row::new(vec![format!("[{}] {key}", position + 1), value.clone()])
+                    .style(Style::default().fg(Color::Cyan));
+            }
             let mut row = Row::new(vec![key.as_str(), value.as_str()]); //todo
             if state
                 .completed_missing_fields
@@ -94,24 +100,50 @@ pub fn render_manual_mapping_p2(area: Rect, buf: &mut Buffer, state: &mut AppSta
     // Render right tab containing missing fields
     state.amount_missing_fields = state.missing_data_fields.len() - 2; // todo
     let mut table_state = TableState::default().with_selected(Some(state.selected_missing_field));
+    // Manual mappings are saved to the custom mapping file, the top layer
+    let custom_layer = layer_name(&state.custom_mapping_path);
     let rows: Vec<Row> = state
         .missing_data_fields
         .iter()
         .enumerate()
         .map(|(index, (key, value))| {
-            let mut row = Row::new(vec![key.as_str(), value.as_str()]);
+            // Number the destinations of a OneToMany mapping in the order the parts are written to them
+            if let Some(position) = state.selected_missing_fields.iter().position(|&field| field == index) {
+                return Row::new(vec![format!("[{}] {key}", position + 1), value.clone(), String::new()])
+                    .style(Style::default().fg(Color::Cyan));
+            }
             if state.completed_missing_fields.iter().any(|&(first, _)| first == index) {
-                row = row.style(Style::default().fg(Color::Green));
+                return Row::new(vec![key.as_str(), value.as_str(), custom_layer.as_str()])
+                    .style(Style::default().fg(Color::Green));
+            }
+            // The fields the mapping files filled are listed below the missing fields, with the layer that filled them
+            match state.missing_field_layers.get(index).filter(|layer| !layer.is_empty()) {
+                Some(layer) => Row::new(vec![key.as_str(), value.as_str(), layer.as_str()])
+                    .style(Style::default().fg(Color::DarkGray)),
+                None => Row::new(vec![key.as_str(), value.as_str(), ""]),
             }
-            row
         })
         .collect();
 
     StatefulWidget::render(
-        Table::new(rows, [Constraint::Percentage(50), Constraint::Percentage(50)])
-            .block(Block::new())
-            .header(Row::new([translate("missing_field"), translate("result_value")]).style(Style::new()))
-            .highlight_style(missingfields_style),
+        Table::new(
+            rows,
+            [
+                Constraint::Percentage(40),
+                Constraint::Percentage(40),
+                Constraint::Percentage(20),
+            ],
+        )
+        .block(Block::new())
+        .header(
+            Row::new([
+                translate("missing_field"),
+                translate("result_value"),
+                translate("layer"),
+            ])
+            .style(Style::new()),
+        )
+        .highlight_style(missingfields_style),
         right_missing_fields,
         buf,
         &mut table_state,
@@ -129,8 +161,9 @@ pub fn render_manual_mapping_p2(area: Rect, buf: &mut Buffer, state: &mut AppSta
             match state.mapping_option {
                 MappingOptions::Transformations => render_popup_mapping(area, buf, state),
                 MappingOptions::OneToMany => render_popup_mapping(area, buf, state), //todo
-                MappingOptions::ManyToOne => render_manytoone_bar(area, buf, state), //todo
-                MappingOptions::DirectCopy => {}                                     // DirectCopy
+                MappingOptions::ManyToOne => render_popup_mapping(area, buf, state),
+                MappingOptions::Constant => render_popup_mapping(area, buf, state),
+                MappingOptions::DirectCopy => {} // DirectCopy
             }
         }
     }
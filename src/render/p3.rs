@@ -6,7 +6,7 @@ use ratatui::{
 };
 
 use crate::{
-    mapping_bars::{render_manytoone_bar, render_mapping_bar},
+    mapping_bars::render_mapping_bar,
     popups::{render_popup_exit_warning, render_popup_mapping},
     state::{translate, AppState, MappingOptions, P2P3Tabs},
 };
@@ -63,6 +63,11 @@ pub fn render_lost_data_p3(area: Rect, buf: &mut Buffer, state: &mut AppState) {
         .iter()
         .enumerate()
         .map(|(index, (key, value))| {
+            // Number the sources of a ManyToOne mapping in the order they are combined
+            if let Some(position) = state.selected_input_fields.iter().position(|&field| field == index) {
+                return Row::new(vec![format!("[{}] {key}", position + 1), value.clone()])
+                    .style(Style::default().fg(Color::Cyan));
+            }
             let mut row = Row::new(vec![key.as_str(), value.as_str()]);
             if state
                 .completed_missing_fields
@@ -97,6 +102,11 @@ pub fn render_lost_data_p3(area: Rect, buf: &mut Buffer, state: &mut AppState) {
         .iter()
         .enumerate()
         .map(|(index, (key, value))| {
+            // Number the destinations of a OneToMany mapping in the order the parts are written to them
+            if let Some(position) = state.selected_optional_fields.iter().position(|&field| field == index) {
+                return Row::new(vec![format!("[{}] {key}", position + 1), value.clone()])
+                    .style(Style::default().fg(Color::Cyan));
+            }
             let mut row = Row::new(vec![key.as_str(), value.as_str()]);
             if state.completed_optional_fields.iter().any(|&(first, _)| first == index) {
                 row = row.style(Style::default().fg(Color::Green));
@@ -128,8 +138,9 @@ pub fn render_lost_data_p3(area: Rect, buf: &mut Buffer, state: &mut AppState) {
             match state.mapping_option {
                 MappingOptions::Transformations => render_popup_mapping(area, buf, state),
                 MappingOptions::OneToMany => render_popup_mapping(area, buf, state), //todo
-                MappingOptions::ManyToOne => render_manytoone_bar(area, buf, state), //todo
-                MappingOptions::DirectCopy => {}                                     // DirectCopy
+                MappingOptions::ManyToOne => render_popup_mapping(area, buf, state),
+                MappingOptions::Constant => render_popup_mapping(area, buf, state),
+                MappingOptions::DirectCopy => {} // DirectCopy
             }
         }
     }
@@ -7,13 +7,14 @@ pub fn render_end_p4(area: Rect, buf: &mut Buffer, state: &mut AppState) {
 
     let vertical_margin = if area.height >= 3 { (area.height - 3) / 2 } else { 0 };
 
-    let txt = format!(
-        "{}: {}\n{}: {}",
-        translate("mapping_complete_1"),
-        state.output_path,
-        translate("mapping_complete_2"),
-        state.custom_mapping_path
-    );
+    let mut txt = format!("{}: {}", translate("mapping_complete_1"), state.output_path);
+    if !state.custom_mapping_path.is_empty() {
+        txt.push_str(&format!(
+            "\n{}: {}",
+            translate("mapping_complete_2"),
+            state.custom_mapping_path
+        ));
+    }
 
     Paragraph::new(txt)
         .centered()
@@ -1,6 +1,6 @@
 use crate::{
-    backend::selector::selector,
-    state::{translate, AppState},
+    backend::{error::MappingError, selector::selector},
+    state::{translate, AppState, MappingOptions},
 };
 
 use ratatui::{
@@ -29,7 +29,19 @@ pub fn render_popup_mapping(mut area: Rect, buf: &mut Buffer, state: &mut AppSta
     state.popup_value_area = left_bottom;
     state.popup_output_result = right_bottom;
 
-    selector(state);
+    // Show the error instead of the popup if the mapping can't be previewed. Invalid parameters are shown as the
+    // result instead, so they can be corrected while typing.
+    let result = match selector(state) {
+        // A pipeline shows the result of every step, the last step is the result of the mapping
+        Ok(()) if !state.candidate_steps.is_empty() => state.candidate_steps.join("\n"),
+        Ok(()) => state.candidate_data_value.clone().unwrap_or_default(),
+        Err(e @ MappingError::InvalidTransformation(_)) => e.to_string(),
+        Err(e) => {
+            state.mapping_error = Some(e);
+            state.popup_mapping_p2_p3 = false;
+            return;
+        }
+    };
 
     Block::new()
         .title(format!("  {}  ", translate("input_path")))
@@ -62,19 +74,55 @@ pub fn render_popup_mapping(mut area: Rect, buf: &mut Buffer, state: &mut AppSta
         .title_alignment(Alignment::Center)
         .render(right_bottom, buf);
 
+    // A ManyToOne mapping has an input path and value for every source
+    let (input_path, input_value) = if state.mapping_option == MappingOptions::ManyToOne && !state.select_mapping_option
+    {
+        let sources = state
+            .selected_input_fields
+            .iter()
+            .map(|&field| &state.input_fields[field])
+            .enumerate();
+        (
+            sources
+                .clone()
+                .map(|(index, (path, _))| format!("[{}] {path}", index + 1))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            sources
+                .map(|(index, (_, value))| format!("[{}] {value}", index + 1))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
+    // A Constant mapping has no input path, its value is the constant or template itself
+    else if state.mapping_option == MappingOptions::Constant && !state.select_mapping_option {
+        (String::new(), state.constant_input.clone())
+    } else {
+        state.input_fields[state.selected_input_field].clone()
+    };
+
+    // A OneToMany mapping has an output path for every part
+    let output_path = if state.mapping_option == MappingOptions::OneToMany && !state.select_mapping_option {
+        state
+            .selected_output_fields()
+            .iter()
+            .enumerate()
+            .map(|(index, &field)| format!("[{}] {}", index + 1, state.output_fields()[field].0))
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        state.output_fields()[state.selected_output_field()].0.clone()
+    };
+
     // Calculate maximum lines used, this sets the maximum scroll offset
     if right.width > 2 {
-        state.popup_amount_lines_path =
-            state.input_fields[state.selected_input_field].0.len() / (right.width as usize - 2);
-        state.popup_amount_lines_value =
-            state.input_fields[state.selected_input_field].1.len() / (right.width as usize - 2);
-        state.popup_amount_lines_output_path =
-            state.missing_data_fields[state.selected_missing_field].0.len() / (right.width as usize - 2);
-        state.popup_amount_lines_result =
-            state.candidate_data_value.as_ref().unwrap().len() / (right.width as usize - 2);
+        state.popup_amount_lines_path = input_path.len() / (right.width as usize - 2);
+        state.popup_amount_lines_value = input_value.len() / (right.width as usize - 2);
+        state.popup_amount_lines_output_path = output_path.len() / (right.width as usize - 2);
+        state.popup_amount_lines_result = result.len() / (right.width as usize - 2);
     }
 
-    Paragraph::new(state.input_fields[state.selected_input_field].0.as_str())
+    Paragraph::new(input_path)
         .wrap(Wrap { trim: false })
         .remove_modifier(Modifier::BOLD)
         .scroll((state.popup_offset_path, 0))
@@ -86,7 +134,7 @@ pub fn render_popup_mapping(mut area: Rect, buf: &mut Buffer, state: &mut AppSta
             buf,
         );
 
-    Paragraph::new(state.input_fields[state.selected_input_field].1.as_str())
+    Paragraph::new(input_value)
         .wrap(Wrap { trim: false })
         .remove_modifier(Modifier::BOLD)
         .scroll((state.popup_offset_value, 0))
@@ -98,7 +146,7 @@ pub fn render_popup_mapping(mut area: Rect, buf: &mut Buffer, state: &mut AppSta
             buf,
         );
 
-    Paragraph::new(state.missing_data_fields[state.selected_missing_field].0.as_str())
+    Paragraph::new(output_path)
         .wrap(Wrap { trim: false })
         .remove_modifier(Modifier::BOLD)
         .scroll((state.popup_offset_output_path, 0))
@@ -110,7 +158,7 @@ pub fn render_popup_mapping(mut area: Rect, buf: &mut Buffer, state: &mut AppSta
             buf,
         );
 
-    Paragraph::new(state.candidate_data_value.as_ref().unwrap().as_str())
+    Paragraph::new(result)
         .wrap(Wrap { trim: false })
         .remove_modifier(Modifier::BOLD)
         .scroll((state.popup_offset_result, 0))
@@ -238,3 +286,73 @@ pub fn render_popup_exit_warning(mut area: Rect, buf: &mut Buffer) {
             buf,
         );
 }
+
+pub fn render_popup_lint_warning(mut area: Rect, buf: &mut Buffer, findings: &[String]) {
+    area = area.inner(&Margin {
+        vertical: 4,
+        horizontal: 28,
+    });
+    Clear.render(area, buf);
+    Block::new()
+        .style(Style::default().fg(Color::Rgb(240, 160, 100)).bg(Color::Black))
+        .borders(Borders::ALL)
+        .render(area, buf);
+
+    let txt = format!("{}\n\n{}", translate("lint_warning"), findings.join("\n"));
+
+    Paragraph::new(txt).wrap(Wrap { trim: false }).render(
+        area.inner(&Margin {
+            vertical: 1,
+            horizontal: 2,
+        }),
+        buf,
+    );
+}
+
+pub fn render_popup_recovery_warning(mut area: Rect, buf: &mut Buffer, saved: &str) {
+    area = area.inner(&Margin {
+        vertical: 8,
+        horizontal: 28,
+    });
+    Clear.render(area, buf);
+    Block::new()
+        .style(Style::default().fg(Color::Rgb(240, 160, 100)).bg(Color::Black))
+        .borders(Borders::ALL)
+        .render(area, buf);
+
+    let txt = format!("{}\n\n{}", translate("recovery_warning"), saved);
+
+    Paragraph::new(txt).centered().wrap(Wrap { trim: false }).render(
+        area.inner(&Margin {
+            vertical: 1,
+            horizontal: 2,
+        }),
+        buf,
+    );
+}
+
+pub fn render_popup_mapping_error(mut area: Rect, buf: &mut Buffer, error: &MappingError) {
+    area = area.inner(&Margin {
+        vertical: 4,
+        horizontal: 28,
+    });
+    Clear.render(area, buf);
+    Block::new()
+        .style(Style::default().fg(Color::Red).bg(Color::Black))
+        .borders(Borders::ALL)
+        .render(area, buf);
+
+    let txt = format!("{}\n\n{}", translate("mapping_error"), error);
+
+    Paragraph::new(txt)
+        .centered()
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: false })
+        .render(
+            area.inner(&Margin {
+                vertical: 1,
+                horizontal: 1,
+            }),
+            buf,
+        );
+}
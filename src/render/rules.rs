@@ -0,0 +1,92 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Rect},
+    prelude::*,
+    widgets::*,
+};
+
+use crate::{
+    popups::render_popup_exit_warning,
+    state::{translate, AppState},
+};
+
+pub fn render_mapping_rules_p4(area: Rect, buf: &mut Buffer, state: &mut AppState) {
+    Block::new()
+        .title(format!("  {}  ", translate("mapping_rules")))
+        .title_alignment(Alignment::Center)
+        .borders(Borders::TOP)
+        .render(area, buf);
+
+    // Layout
+    let [_title, page, bottom] =
+        Layout::vertical(vec![Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]).areas(area);
+    let [mut left_rules, mut right_rule] =
+        Layout::horizontal(vec![Constraint::Percentage(50), Constraint::Min(0)]).areas(page);
+    Block::new().borders(Borders::RIGHT).render(left_rules, buf);
+
+    // Inner blocks for margins
+    left_rules = left_rules.inner(&Margin {
+        vertical: 0,
+        horizontal: 1,
+    });
+    right_rule = right_rule.inner(&Margin {
+        vertical: 0,
+        horizontal: 1,
+    });
+
+    // Render left list of confirmed rules, in the order they are applied
+    if state.mappings.is_empty() {
+        Paragraph::new(translate("no_rules"))
+            .wrap(Wrap { trim: true })
+            .render(left_rules, buf);
+    } else {
+        let mut table_state = TableState::default().with_selected(Some(state.selected_rule));
+        let rows: Vec<Row> = state
+            .mappings
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| Row::new(vec![rule.describe(index)]))
+            .collect();
+
+        StatefulWidget::render(
+            Table::new(rows, [Constraint::Percentage(100)])
+                .block(Block::new())
+                .highlight_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            left_rules,
+            buf,
+            &mut table_state,
+        );
+    }
+
+    // Render the selected rule, or the JSON that is edited with the cursor in it
+    if let Some(input) = &state.rule_input {
+        let cursor = input
+            .char_indices()
+            .nth(state.rule_cursor)
+            .map_or(input.len(), |(index, _)| index);
+        let txt = format!(
+            "{}\n\n{}│{}",
+            translate("edit_rule"),
+            &input[..cursor],
+            &input[cursor..]
+        );
+
+        Paragraph::new(txt)
+            .style(Style::default().fg(Color::Yellow))
+            .wrap(Wrap { trim: false })
+            .render(right_rule, buf);
+    } else if let Some(rule) = state.mappings.get(state.selected_rule) {
+        Paragraph::new(serde_json::to_string_pretty(rule).unwrap_or_default())
+            .wrap(Wrap { trim: false })
+            .render(right_rule, buf);
+    }
+
+    Paragraph::new(translate("rules_keys"))
+        .style(Style::default().fg(Color::White))
+        .render(bottom, buf);
+
+    // Render warning if user wants to exit.
+    if state.exit_warning {
+        render_popup_exit_warning(area, buf);
+    }
+}
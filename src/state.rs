@@ -1,8 +1,12 @@
 use ratatui::layout::Rect;
-use std::borrow::Cow;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, path::Path, time::Instant};
 use strum::{AsRefStr, Display, FromRepr};
 
-use crate::backend::{repository::Repository, transformations::Transformation};
+use crate::{
+    backend::{error::MappingError, repository::Repository, spine::SPINE_REGISTRY, transformations::Transformation},
+    events::history::History,
+};
 
 //////////      STRUCTS & ENUMS     //////////
 
@@ -18,9 +22,10 @@ pub struct AppState {
 
     // Paths
     pub input_path: String,
-    pub mapping_path: String,
+    pub mapping_paths: Vec<String>, // Mapping files from the lowest layer to the highest, the last one is typed on P1
     pub output_path: String,
     pub custom_mapping_path: String,
+    pub session_path: String, // Session file that is saved with Ctrl+S and can be resumed on P1
 
     // Tabs for P2 and P3
     pub p2_p3_tabs: P2P3Tabs,
@@ -29,41 +34,65 @@ pub struct AppState {
 
     // Popups
     pub overwrite_warning: bool,
+    pub lint_warning: Vec<String>, // Findings of the linter, shown before the mapping is loaded
+    pub recovery_warning: Option<String>, // Time of the autosaved session that can be recovered
     pub uncompleted_warning: bool,
     pub popup_mapping_p2_p3: bool,
     pub exit_warning: bool,
+    pub mapping_error: Option<MappingError>,
 
     // Mapping options
     pub mapping_option: MappingOptions,
     pub transformations: Transformations,
     pub selected_transformation: usize,
     pub selected_transformations: Vec<Transformations>,
+    pub parameters_input: Option<Transformations>, // Slice or Regex while its parameters are entered
+    pub second_parameter: bool,
+    pub slice_input: (String, String),
+    pub regex_input: (String, String), // (pattern, replacement)
+    pub timezone_input: String,
+    pub lookup_input: (String, String),    // (table, default)
+    pub condition_input: (String, String), // (path, value)
     pub dividers: String,
+    pub combiner: Combiners,
+    pub separator: String,
+    pub constant_input: String, // JSON value, text or template of a Constant mapping
 
     // Fields extracted from the input json file.
     pub input_fields: Vec<(String, String)>,
     pub amount_input_fields: usize,
     pub selected_input_field: usize,
-    pub selected_input_fields: Vec<String>, //string or usize? For ManyToOne, currently not in use
+    pub selected_input_fields: Vec<usize>, // Sources of a ManyToOne mapping, in the order they are combined
 
-    // Mandatory fields extracted from the output json format
+    // Mandatory fields extracted from the output json format, followed by the fields the mapping files filled
     pub missing_data_fields: Vec<(String, String)>,
     pub amount_missing_fields: usize,
     pub selected_missing_field: usize,
-    pub selected_missing_fields: Vec<String>, //string or usize? For ManyToOne, currently not in use
+    pub selected_missing_fields: Vec<usize>, // Destinations of a OneToMany mapping, in the order of the parts
     pub completed_missing_fields: Vec<(usize, usize)>, // (missing_field_index, input_field_index)
+    pub missing_field_layers: Vec<String>, // Layer that filled each missing field, empty when no mapping file filled it
 
     // Optional fields extracted from the output json format
     pub optional_fields: Vec<(String, String)>,
     pub amount_optional_fields: usize,
     pub selected_optional_field: usize,
-    pub selected_optional_fields: Vec<String>, //string or usize? For ManyToOne, currently not in use
+    pub selected_optional_fields: Vec<usize>, // Destinations of a OneToMany mapping, in the order of the parts
     pub completed_optional_fields: Vec<(usize, usize)>, // (optional_field_index, input_field_index)
 
     // Backend
     pub candidate_data_value: Option<String>,
+    pub candidate_steps: Vec<String>, // Intermediate results of a pipeline, one line per step
     pub repository: Repository,
-    pub mappings: Vec<Transformation>,
+    pub loaded_repository: Repository, // The repository after the mapping file is applied, before the manual mappings
+    pub mappings: Vec<Transformation>, // Confirmed manual mappings, at most one per destination
+    pub history: History,              // Undo and redo of the mapping actions on P2 and P3
+    pub autosaved: Option<Instant>,
+    pub saved_mappings: String, // JSON of the mappings as they were last saved to or resumed from the session file
+
+    // Mapping rules on P4
+    pub selected_rule: usize,
+    pub rule_input: Option<String>, // JSON of the rule that is edited
+    pub rule_cursor: usize,         // Position of the cursor in the rule input, in characters
 
     pub area: Rect,
 
@@ -146,29 +175,38 @@ pub enum P1Prompts {
     MappingFile,
     Mapping,
     CustomMapping,
+    Session,
 }
 
-#[derive(Clone, Copy, FromRepr, Debug, Default, PartialEq)]
+#[derive(Clone, Copy, FromRepr, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum Mapping {
     OBv3ToELM = 0,
     #[default]
     ELMToOBv3,
+    // The pairs of the spine registry, composed of the mapping into the spine and the mapping out of it
+    OBv3ToELMViaSpine,
+    ELMToOBv3ViaSpine,
 }
 
 impl Mapping {
     pub fn input_format(&self) -> String {
         match self {
-            Mapping::OBv3ToELM => "OBv3".to_string(),
-            Mapping::ELMToOBv3 => "ELM".to_string(),
+            Mapping::OBv3ToELM | Mapping::OBv3ToELMViaSpine => "OBv3".to_string(),
+            Mapping::ELMToOBv3 | Mapping::ELMToOBv3ViaSpine => "ELM".to_string(),
         }
     }
 
     pub fn output_format(&self) -> String {
         match self {
-            Mapping::OBv3ToELM => "ELM".to_string(),
-            Mapping::ELMToOBv3 => "OBv3".to_string(),
+            Mapping::OBv3ToELM | Mapping::OBv3ToELMViaSpine => "ELM".to_string(),
+            Mapping::ELMToOBv3 | Mapping::ELMToOBv3ViaSpine => "OBv3".to_string(),
         }
     }
+
+    /// Whether the mapping is composed from the spine registry, which is then the lowest layer of mapping files.
+    pub fn via_spine(&self) -> bool {
+        matches!(self, Mapping::OBv3ToELMViaSpine | Mapping::ELMToOBv3ViaSpine)
+    }
 }
 
 #[derive(Clone, Copy, FromRepr, Debug, Default, PartialEq, Display)]
@@ -178,6 +216,7 @@ pub enum MappingOptions {
     Transformations,
     OneToMany,
     ManyToOne,
+    Constant,
 }
 #[derive(Clone, Copy, FromRepr, Debug, Default, PartialEq, Display)]
 pub enum Transformations {
@@ -187,15 +226,117 @@ pub enum Transformations {
     UpperCase,
     Slice,
     Regex,
+    Trim,
+    DateTime,
+    Lookup,
+    Text,
+    Number,
+    Boolean,
+    Wrap,
+    Unwrap,
+    Condition,
 }
 
-#[derive(Clone, Copy, FromRepr, Debug, Default, PartialEq)]
+#[derive(Clone, Copy, FromRepr, Debug, Default, PartialEq, Display)]
+pub enum Combiners {
+    #[default]
+    Concat,
+    Array,
+}
+
+#[derive(Clone, Copy, FromRepr, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub enum Pages {
     #[default]
     InputPromptsP1 = 0,
     ManualMappingP2,
     UnusedDataP3,
-    EndP4,
+    MappingRulesP4,
+    EndP5,
+}
+
+impl AppState {
+    /// Returns the layers of mapping files that are applied: the spine registry when the mapping goes through the spine,
+    /// the mapping files of P1 and the custom mapping file on top, when one is given and it exists. New mappings are
+    /// saved to the custom mapping file.
+    pub fn layer_paths(&self) -> Vec<String> {
+        let mut paths = vec![];
+        if self.mapping.via_spine() {
+            paths.push(SPINE_REGISTRY.to_string());
+        }
+        paths.extend(self.mapping_paths.iter().cloned());
+        if Path::new(&self.custom_mapping_path).is_file() {
+            paths.push(self.custom_mapping_path.clone());
+        }
+        paths
+    }
+
+    /// Whether the mapping files of P1 are given and exist. Through the spine, the mapping files are optional.
+    pub fn mapping_files_exist(&self) -> bool {
+        (self.mapping.via_spine() || !self.mapping_paths.is_empty())
+            && self.mapping_paths.iter().all(|path| Path::new(path).is_file())
+    }
+
+    /// Whether every missing field that no mapping file filled is completed.
+    pub fn missing_fields_completed(&self) -> bool {
+        self.missing_field_layers
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, layer)| layer.is_empty())
+            .all(|(field, _)| self.completed_missing_fields.iter().any(|&(first, _)| first == field))
+    }
+
+    /// Returns the output fields of the current page, the missing fields on P2 and the optional fields on P3.
+    pub fn output_fields(&self) -> &Vec<(String, String)> {
+        match self.page {
+            Pages::UnusedDataP3 => &self.optional_fields,
+            _ => &self.missing_data_fields,
+        }
+    }
+
+    pub fn output_fields_mut(&mut self) -> &mut Vec<(String, String)> {
+        match self.page {
+            Pages::UnusedDataP3 => &mut self.optional_fields,
+            _ => &mut self.missing_data_fields,
+        }
+    }
+
+    /// Returns the index of the active output field on the current page.
+    pub fn selected_output_field(&self) -> usize {
+        match self.page {
+            Pages::UnusedDataP3 => self.selected_optional_field,
+            _ => self.selected_missing_field,
+        }
+    }
+
+    pub fn selected_output_field_mut(&mut self) -> &mut usize {
+        match self.page {
+            Pages::UnusedDataP3 => &mut self.selected_optional_field,
+            _ => &mut self.selected_missing_field,
+        }
+    }
+
+    /// Returns the output fields that are selected as destinations on the current page.
+    pub fn selected_output_fields(&self) -> &Vec<usize> {
+        match self.page {
+            Pages::UnusedDataP3 => &self.selected_optional_fields,
+            _ => &self.selected_missing_fields,
+        }
+    }
+
+    pub fn selected_output_fields_mut(&mut self) -> &mut Vec<usize> {
+        match self.page {
+            Pages::UnusedDataP3 => &mut self.selected_optional_fields,
+            _ => &mut self.selected_missing_fields,
+        }
+    }
+
+    pub fn completed_output_fields_mut(&mut self) -> &mut Vec<(usize, usize)> {
+        match self.page {
+            Pages::UnusedDataP3 => &mut self.completed_optional_fields,
+            _ => &mut self.completed_missing_fields,
+        }
+    }
 }
 
 //////////      HELPERS     //////////
@@ -256,9 +397,10 @@ macro_rules! next_prev {
 // }
 
 next_prev!(Languages, Languages::EN, Languages::SV);
-next_prev!(Mapping, Mapping::OBv3ToELM, Mapping::ELMToOBv3);
-next_prev!(Pages, Pages::InputPromptsP1, Pages::EndP4);
-next_prev!(P1Prompts, P1Prompts::Language, P1Prompts::CustomMapping);
-next_prev!(Transformations, Transformations::LowerCase, Transformations::Regex);
-next_prev!(MappingOptions, MappingOptions::DirectCopy, MappingOptions::ManyToOne);
+next_prev!(Mapping, Mapping::OBv3ToELM, Mapping::ELMToOBv3ViaSpine);
+next_prev!(Pages, Pages::InputPromptsP1, Pages::EndP5);
+next_prev!(P1Prompts, P1Prompts::Language, P1Prompts::Session);
+next_prev!(Transformations, Transformations::LowerCase, Transformations::Condition);
+next_prev!(MappingOptions, MappingOptions::DirectCopy, MappingOptions::Constant);
+next_prev!(Combiners, Combiners::Concat, Combiners::Array);
 next_prev!(P2P3Tabs, P2P3Tabs::InputFields, P2P3Tabs::View);